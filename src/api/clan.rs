@@ -1,10 +1,14 @@
-use crate::util::Config;
+use crate::{core::tag::ClanTag, util::Config};
 use reqwest::{Client, header::AUTHORIZATION};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Duration};
 use void_log::{log_info, log_warn};
 
+/// # 接口请求间隔
+/// 批量查询时每次请求后等待，避免触发限流
+pub const API_INTERVAL: Duration = Duration::from_millis(200);
+
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Clan {
@@ -38,6 +42,7 @@ pub struct Clan {
     #[serde(rename = "requiredTownhallLevel")]
     pub required_town_hall_level: Option<i64>,
     pub clan_capital: Option<ClanCapital>,
+    pub chat_language: Option<ClanChatLanguage>,
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
@@ -134,12 +139,11 @@ pub struct ClanChatLanguage {
 }
 
 impl Clan {
-    pub async fn get(tag: &str) -> Self {
+    pub async fn get(tag: &ClanTag) -> Self {
         let coc_api = Config::get().await.get_api();
         let token = format!("Bearer {}", coc_api.token.unwrap_or_default());
-        let tag = tag.replace("#", "").to_uppercase();
-        log_info!("查询标签 #{}", &tag);
-        let url = format!("https://api.clashofclans.com/v1/clans/%23{tag}");
+        log_info!("查询标签 {}", tag);
+        let url = format!("https://api.clashofclans.com/v1/clans/{}", tag.encoded());
         log_info!("API {}", &url);
         let response = Client::new()
             .get(url)
@@ -169,10 +173,21 @@ impl Clan {
         };
         towns
     }
+
+    /// # 平均本等级
+    pub fn th_avg(&self) -> f32 {
+        let towns = self.info();
+        let count: i64 = towns.values().sum();
+        if count == 0 {
+            return 0.0;
+        }
+        let total: i64 = towns.iter().map(|(th, n)| th * n).sum();
+        total as f32 / count as f32
+    }
 }
 
 #[tokio::test]
 async fn test1() {
-    let c = Clan::get("#q82u2qr9").await;
+    let c = Clan::get(&ClanTag::new("#q82u2qr9").unwrap()).await;
     log_info!("{c:?}")
 }
@@ -23,6 +23,26 @@ where
 }
 
 impl MiddleReadCompo {
+    pub fn get_min_th_avg(&self) -> f32 {
+        self.min_th_avg
+    }
+
+    pub fn get_max_th_avg(&self) -> f32 {
+        self.max_th_avg
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.global
+    }
+
+    /// # 定时任务使用，请求失败不panic
+    pub async fn try_get() -> Result<Self, reqwest::Error> {
+        reqwest::get("http://cocbzlm.com:8422/api/accinfo/readCompo")
+            .await?
+            .json()
+            .await
+    }
+
     pub async fn get() -> Self {
         let response = reqwest::get("http://cocbzlm.com:8422/api/accinfo/readCompo")
             .await
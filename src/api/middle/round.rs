@@ -1,4 +1,4 @@
-use crate::orange::Round;
+use crate::orange::{Round, RoundNew};
 use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -56,9 +56,11 @@ impl MiddleRoundApi {
         if self.current_round.eq(&self.future_round) {
             return Err("轮次未更新".to_string());
         }
-        let res = Round::insert(&self.future_sync_time, pool)
+        // 中间库时间为北京时间
+        let round_new = RoundNew::new(&self.future_sync_time, "Asia/Shanghai");
+        let res = Round::insert(&round_new, pool)
             .await
-            .unwrap_or_default()
+            .map_err(|e| e.msg_cn())?
             .rows_affected();
         Ok(res)
     }
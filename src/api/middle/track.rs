@@ -1,5 +1,5 @@
 use crate::{
-    core::registration::ClanTag,
+    core::tag::ClanTag,
     orange::{Clan, ClanStatus, Track, TrackResult, TrackType},
 };
 use reqwest::Client;
@@ -8,7 +8,7 @@ use serde_json::json;
 use sqlx::{Pool, Postgres};
 use std::fmt::{Display, Formatter};
 use uuid::Uuid;
-use void_log::log_info;
+use void_log::{log_info, log_warn};
 
 pub const BZ_UUID: &str = "4fc2832d-cf1f-47e0-9b54-6c35937c73a4";
 
@@ -54,27 +54,6 @@ impl Display for MiddleTrackApi {
     }
 }
 
-impl ClanTag {
-    pub async fn middle_api(&self) -> Result<MiddleTrackApi, ()> {
-        let body = json!({
-            "myTag": self.tag,
-            "isGlobal": self.is_global,
-        });
-        let response = Client::new()
-            .post("http://cocbzlm.com:8422/api/wardecider")
-            .header("isAdmin", "true")
-            .json(&body)
-            .send()
-            .await
-            .unwrap();
-        if response.status().is_success() {
-            Ok(response.json().await.unwrap())
-        } else {
-            Err(())
-        }
-    }
-}
-
 impl MiddleTrackApi {
     pub async fn new(clan: &Clan, is_global: bool) -> Result<Self, ()> {
         let body = json!({
@@ -101,12 +80,15 @@ impl MiddleTrackApi {
         mut track: Track,
         is_global: bool,
         self_clan: &Clan,
-    ) -> Track {
+    ) -> Option<Track> {
         log_info!("{}", &self);
 
         // 格式化对方tag(不战可能反转了my_tag)
-        let my_tag = format!("#{}", self.my_tag.replace("#", ""));
-        let opp_tag = format!("#{}", self.opp_tag.replace("#", ""));
+        let (Ok(my_tag), Ok(opp_tag)) = (ClanTag::new(&self.my_tag), ClanTag::new(&self.opp_tag))
+        else {
+            log_warn!("中间库标签错误 {} | {}", self.my_tag, self.opp_tag);
+            return None;
+        };
         let (rival_tag, rival_name, self_name) =
             if self_clan.tag.as_ref().is_some_and(|tag| my_tag.eq(tag)) {
                 (opp_tag, self.opp_name.clone(), self.my_name.clone())
@@ -119,10 +101,10 @@ impl MiddleTrackApi {
         track.self_name = self_name;
         track.self_now_point = track.self_history_point;
 
-        // 格式化输赢tag
-        let win_tag = format!("#{}", self.win_tag.replace("#", ""));
+        // 格式化输赢tag，无胜方为空
+        let win_tag = ClanTag::new(&self.win_tag).ok();
 
-        log_info!("rival_tag: {rival_tag} | win_tag: {win_tag} | is_global: {is_global}");
+        log_info!("rival_tag: {rival_tag} | win_tag: {win_tag:?} | is_global: {is_global}");
 
         let (status, series_id) = if self.err {
             (ClanStatus::Other, None)
@@ -159,7 +141,7 @@ impl MiddleTrackApi {
 
         // 判断输赢写入Track
         if let Some(rct) = track.rival_tag.as_ref() {
-            if win_tag.eq(rct) {
+            if win_tag.as_ref() == Some(rct) {
                 track.result = TrackResult::Lose;
                 track.r#type = TrackType::Alliance;
             } else if self.err {
@@ -175,6 +157,6 @@ impl MiddleTrackApi {
         };
 
         // 返回Track
-        track
+        Some(track)
     }
 }
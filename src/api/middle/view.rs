@@ -1,4 +1,4 @@
-use crate::middle;
+use crate::{core::tag::ClanTag, middle};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
@@ -13,7 +13,7 @@ pub struct MiddleViewApi {
     pub details: Vec<MiddleViewApiDetails>,
     pub summary: Vec<String>,
     #[serde(skip_deserializing)]
-    pub tag: String,
+    pub tag: Option<ClanTag>,
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
@@ -34,10 +34,9 @@ pub struct MiddleViewApiDetails {
 }
 
 impl MiddleViewApi {
-    pub async fn get(tag: &str) -> Self {
-        let tag = format!("#{}", tag.replace("#", "").to_uppercase());
+    pub async fn get(tag: &ClanTag) -> Self {
         let mut url = Url::parse("http://cocbzlm.com:8422/api/accinfo/scores").unwrap();
-        url.query_pairs_mut().append_pair("clanTag", &tag);
+        url.query_pairs_mut().append_pair("clanTag", tag.as_str());
         url.query_pairs_mut().append_pair("isGlobal", "true");
         log_info!("{}", &url);
 
@@ -47,14 +46,13 @@ impl MiddleViewApi {
             .json::<Self>()
             .await
             .expect("failed to parse API response");
-        api.tag = tag;
+        api.tag = Some(tag.clone());
         api
     }
 
-    pub async fn get_text(tag: &str) -> String {
-        let tag = format!("#{}", tag.replace("#", "").to_uppercase());
+    pub async fn get_text(tag: &ClanTag) -> String {
         let mut url = Url::parse("http://cocbzlm.com:8422/api/accinfo/scores").unwrap();
-        url.query_pairs_mut().append_pair("clanTag", &tag);
+        url.query_pairs_mut().append_pair("clanTag", tag.as_str());
         url.query_pairs_mut().append_pair("isGlobal", "true");
         log_info!("{}", &url);
 
@@ -66,15 +64,17 @@ impl MiddleViewApi {
             .expect("failed to parse API response")
     }
 
-    pub fn self_to_database(self) -> middle::Track {
+    pub fn self_to_database(self, tag: &ClanTag) -> middle::Track {
         middle::Track {
+            id: Default::default(),
             server: self.server,
             bz_total_score: self.bz_total_score,
             public_total_score: self.public_total_score,
             details: Json(self.details),
             summary: self.summary,
-            tag: self.tag,
-            ..Default::default()
+            create_time: Default::default(),
+            update_time: Default::default(),
+            tag: tag.clone(),
         }
     }
 }
@@ -82,13 +82,14 @@ impl MiddleViewApi {
 #[tokio::test]
 async fn test() {
     let pool = crate::util::Config::get().await.get_database().get().await;
-    let a = MiddleViewApi::get("#2J9999990").await.self_to_database();
+    let tag = ClanTag::new("#2J9999990").unwrap();
+    let a = MiddleViewApi::get(&tag).await.self_to_database(&tag);
     let b = a.insert(&pool).await.unwrap();
     log_info!("{a:?} {}", b.rows_affected());
 }
 
 #[tokio::test]
 async fn test2() {
-    let a = MiddleViewApi::get_text("#8Q0VQJ2P").await;
+    let a = MiddleViewApi::get_text(&ClanTag::new("#8Q0VQJ2P").unwrap()).await;
     log_info!("{a:?}");
 }
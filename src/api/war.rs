@@ -1,5 +1,6 @@
-use crate::{api::clan::ClanIconUrls, util::Config};
+use crate::{api::clan::ClanIconUrls, core::tag::ClanTag, util::Config};
 use axum::http::header::AUTHORIZATION;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -12,13 +13,13 @@ pub struct War {
     pub message: Option<String>,
     pub detail: Option<Value>,
     pub r#type: Option<String>,
-    state: Option<String>,
-    team_size: Option<i64>,
-    attacks_per_member: Option<i64>,
+    pub state: Option<String>,
+    pub team_size: Option<i64>,
+    pub attacks_per_member: Option<i64>,
     battle_modifier: Option<String>,
     preparation_start_time: Option<String>,
     start_time: Option<String>,
-    end_time: Option<String>,
+    pub end_time: Option<String>,
     pub clan: Option<WarClan>,
     pub opponent: Option<WarClan>,
 }
@@ -28,33 +29,65 @@ pub struct War {
 pub struct WarClan {
     pub tag: Option<String>,
     pub name: Option<String>,
-    badge_urls: Option<ClanIconUrls>,
-    clan_level: Option<i64>,
-    attacks: Option<i64>,
-    stars: Option<i64>,
-    destruction_percentage: Option<f64>,
-    members: Option<Vec<WarClanMember>>,
-    exp_earned: Option<i64>,
+    pub badge_urls: Option<ClanIconUrls>,
+    pub clan_level: Option<i64>,
+    pub attacks: Option<i64>,
+    pub stars: Option<i64>,
+    pub destruction_percentage: Option<f64>,
+    pub members: Option<Vec<WarClanMember>>,
+    pub exp_earned: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
-struct WarClanMember {
-    tag: Option<String>,
-    name: Option<String>,
+pub struct WarClanMember {
+    pub tag: Option<String>,
+    pub name: Option<String>,
     #[serde(rename = "townhallLevel")]
-    town_hall_level: Option<i64>,
-    map_position: Option<i64>,
-    opponent_attacks: Option<i64>,
+    pub town_hall_level: Option<i64>,
+    pub map_position: Option<i64>,
+    pub opponent_attacks: Option<i64>,
+    pub attacks: Option<Vec<WarMemberAttack>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct WarMemberAttack {
+    pub attacker_tag: Option<String>,
+    pub defender_tag: Option<String>,
+    pub stars: Option<i64>,
+    pub destruction_percentage: Option<f64>,
+    pub order: Option<i64>,
+    pub duration: Option<i64>,
 }
 
 impl War {
-    pub async fn get(tag: &str) -> Self {
+    /// # 对战已结束
+    pub fn is_ended(&self) -> bool {
+        self.state.as_deref() == Some("warEnded")
+    }
+
+    /// # 结束时间
+    pub fn end_time_utc(&self) -> Option<DateTime<Utc>> {
+        let end_time = self.end_time.as_deref()?;
+        NaiveDateTime::parse_from_str(end_time, "%Y%m%dT%H%M%S%.3fZ")
+            .ok()
+            .map(|dt| dt.and_utc())
+    }
+
+    /// # 每方可用进攻次数
+    pub fn attacks_available(&self) -> i64 {
+        self.team_size.unwrap_or_default() * self.attacks_per_member.unwrap_or(2)
+    }
+
+    pub async fn get(tag: &ClanTag) -> Self {
         let coc_api = Config::get().await.get_api();
         let token = format!("Bearer {}", coc_api.token.unwrap_or_default());
-        let tag = tag.replace("#", "").to_uppercase();
-        log_info!("查询标签 #{}", &tag);
-        let url = format!("https://api.clashofclans.com/v1/clans/%23{tag}/currentwar");
+        log_info!("查询标签 {}", tag);
+        let url = format!(
+            "https://api.clashofclans.com/v1/clans/{}/currentwar",
+            tag.encoded()
+        );
         log_info!("API {}", &url);
         let response = Client::new()
             .get(url)
@@ -73,7 +106,7 @@ impl War {
 
 #[tokio::test]
 async fn test_get_war() {
-    War::get("#2G2GJRQQJ").await;
+    War::get(&ClanTag::new("#2G2GJRQQJ").unwrap()).await;
 }
 
 #[tokio::test]
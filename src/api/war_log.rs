@@ -1,4 +1,5 @@
 use crate::api::war::WarClan;
+use crate::core::tag::ClanTag;
 use crate::util::Config;
 use axum::http::header::AUTHORIZATION;
 use chrono::{DateTime, NaiveDateTime, Utc};
@@ -44,12 +45,14 @@ struct WarLogPagingCursors {
 }
 
 impl WarLog {
-    pub async fn get(tag: &str, limit: u64) -> Self {
+    pub async fn get(tag: &ClanTag, limit: u64) -> Self {
         let coc_api = Config::get().await.get_api();
         let token = format!("Bearer {}", coc_api.token.unwrap_or_default());
-        let tag = tag.replace("#", "").to_uppercase();
-        log_info!("查询标签 #{}", &tag);
-        let url = format!("https://api.clashofclans.com/v1/clans/%23{tag}/warlog?limit={limit}");
+        log_info!("查询标签 {}", tag);
+        let url = format!(
+            "https://api.clashofclans.com/v1/clans/{}/warlog?limit={limit}",
+            tag.encoded()
+        );
         log_info!("API {}", &url);
         let response = Client::new()
             .get(url)
@@ -1 +1,3 @@
 pub mod registration;
+pub mod schedule;
+pub mod tag;
@@ -0,0 +1,107 @@
+use crate::orange::{Blacklist, ClanLock, ClanProfile, PlayerBlacklist, Round, WarStat};
+use sqlx::{Pool, Postgres};
+use std::time::Duration;
+use tokio::time::interval;
+use void_log::{log_error, log_info};
+
+/// # 部落同步间隔
+const CLAN_SYNC: Duration = Duration::from_secs(60 * 60);
+
+/// # 准入检查间隔
+const CLAN_ELIGIBILITY: Duration = Duration::from_secs(60 * 60);
+
+/// # 黑名单到期检查间隔
+const BLACKLIST_EXPIRE: Duration = Duration::from_secs(10 * 60);
+
+/// # 对战阵容筛查间隔
+const WAR_SCREEN: Duration = Duration::from_secs(30 * 60);
+
+/// # 对战统计抓取间隔
+const WAR_STAT: Duration = Duration::from_secs(30 * 60);
+
+/// # 轮次状态检查间隔
+const ROUND_STATE: Duration = Duration::from_secs(60);
+
+/// # 启动定时任务
+pub fn start(pool: Pool<Postgres>) {
+    tokio::spawn(clan_sync(pool.clone()));
+    tokio::spawn(clan_eligibility(pool.clone()));
+    tokio::spawn(blacklist_expire(pool.clone()));
+    tokio::spawn(war_screen(pool.clone()));
+    tokio::spawn(war_stat(pool.clone()));
+    tokio::spawn(round_state(pool));
+}
+
+/// # 部落资料同步
+async fn clan_sync(pool: Pool<Postgres>) {
+    let mut interval = interval(CLAN_SYNC);
+    loop {
+        interval.tick().await;
+        log_info!("定时任务: 部落同步");
+        if let Err(e) = ClanProfile::sync(&pool).await {
+            log_error!("Clan Sync {e}");
+        }
+    }
+}
+
+/// # 部落准入检查
+async fn clan_eligibility(pool: Pool<Postgres>) {
+    let mut interval = interval(CLAN_ELIGIBILITY);
+    loop {
+        interval.tick().await;
+        log_info!("定时任务: 准入检查");
+        if let Err(e) = ClanLock::check_all(&pool).await {
+            log_error!("Clan Eligibility {e}");
+        }
+    }
+}
+
+/// # 黑名单到期解除
+async fn blacklist_expire(pool: Pool<Postgres>) {
+    let mut interval = interval(BLACKLIST_EXPIRE);
+    loop {
+        interval.tick().await;
+        match Blacklist::expire_all(&pool).await {
+            Ok(count) if count > 0 => log_info!("黑名单到期解除 {count}"),
+            Ok(_) => {}
+            Err(e) => log_error!("Blacklist Expire {e}"),
+        }
+    }
+}
+
+/// # 对战阵容黑名单筛查
+async fn war_screen(pool: Pool<Postgres>) {
+    let mut interval = interval(WAR_SCREEN);
+    loop {
+        interval.tick().await;
+        match PlayerBlacklist::screen_wars(&pool).await {
+            Ok(count) if count > 0 => log_info!("黑名单玩家提醒 {count}"),
+            Ok(_) => {}
+            Err(e) => log_error!("War Screen {e}"),
+        }
+    }
+}
+
+/// # 对战结果统计
+async fn war_stat(pool: Pool<Postgres>) {
+    let mut interval = interval(WAR_STAT);
+    loop {
+        interval.tick().await;
+        if let Err(e) = WarStat::capture_all(&pool).await {
+            log_error!("War Stat {e}");
+        }
+    }
+}
+
+/// # 轮次状态推进
+async fn round_state(pool: Pool<Postgres>) {
+    let mut interval = interval(ROUND_STATE);
+    loop {
+        interval.tick().await;
+        match Round::advance_all(&pool).await {
+            Ok(count) if count > 0 => log_info!("轮次状态变更 {count}"),
+            Ok(_) => {}
+            Err(e) => log_error!("Round State {e}"),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use sqlx::Type;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// # 游戏标签
+/// 统一为 `#` 开头的大写形式，只允许游戏标签字符
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClanTag(String);
+
+/// # 玩家标签
+/// 与部落标签同一套字符规则
+pub type PlayerTag = ClanTag;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagError {
+    Empty,
+    InvalidChar(char),
+}
+
+impl Display for TagError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagError::Empty => write!(f, "Tag is empty"),
+            TagError::InvalidChar(c) => write!(f, "Tag has invalid char '{c}'"),
+        }
+    }
+}
+
+impl ClanTag {
+    /// # 标签字符表
+    const ALPHABET: &'static str = "0289PYLQGRJCUV";
+
+    /// # 标准化并校验
+    /// 去掉 `#`、转大写，字母O按数字0处理
+    pub fn new(tag: &str) -> Result<Self, TagError> {
+        let code = tag
+            .trim()
+            .trim_start_matches('#')
+            .to_uppercase()
+            .replace('O', "0");
+        if code.is_empty() {
+            return Err(TagError::Empty);
+        }
+        if let Some(c) = code.chars().find(|c| !Self::ALPHABET.contains(*c)) {
+            return Err(TagError::InvalidChar(c));
+        }
+        Ok(Self(format!("#{code}")))
+    }
+
+    /// # 带 `#` 的标签
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// # 不带 `#` 的标签
+    pub fn code(&self) -> &str {
+        self.0.trim_start_matches('#')
+    }
+
+    /// # 接口路径用
+    pub fn encoded(&self) -> String {
+        format!("%23{}", self.code())
+    }
+
+    /// # 标签数值
+    /// 按字符表做14进制解码
+    pub fn id(&self) -> u64 {
+        self.code().chars().fold(0, |id, c| {
+            let digit = Self::ALPHABET.find(c).unwrap_or_default() as u64;
+            id.wrapping_mul(14).wrapping_add(digit)
+        })
+    }
+
+    /// # 是否先手
+    /// 数值小的一方为先手，与谁先登记、谁来登记无关
+    pub fn is_first_mover(&self, rival: &ClanTag) -> bool {
+        (self.id(), self.as_str()) <= (rival.id(), rival.as_str())
+    }
+}
+
+impl Display for ClanTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ClanTag {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for ClanTag {
+    type Error = TagError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<ClanTag> for String {
+    fn from(value: ClanTag) -> Self {
+        value.0
+    }
+}
+
+#[test]
+fn test_clan_tag() {
+    let tag = ClanTag::new("#q82u2qr9").unwrap();
+    assert_eq!(tag.as_str(), "#Q82U2QR9");
+    assert_eq!(tag.code(), "Q82U2QR9");
+    assert_eq!(tag.encoded(), "%23Q82U2QR9");
+    assert_eq!(ClanTag::new(" q82u2qr9 ").unwrap(), tag);
+    assert_eq!(ClanTag::new("#2O9").unwrap().as_str(), "#209");
+    assert_eq!(ClanTag::new("#"), Err(TagError::Empty));
+    assert_eq!(ClanTag::new("#ABC"), Err(TagError::InvalidChar('A')));
+
+    let json: ClanTag = serde_json::from_str("\"#2lUUU8qp8\"").unwrap();
+    assert_eq!(json.as_str(), "#2LUUU8QP8");
+    assert_eq!(serde_json::to_string(&json).unwrap(), "\"#2LUUU8QP8\"");
+    assert!(serde_json::from_str::<ClanTag>("\"#hello\"").is_err());
+}
+
+#[test]
+fn test_first_mover() {
+    assert_eq!(ClanTag::new("#2").unwrap().id(), 1);
+    assert_eq!(ClanTag::new("#20").unwrap().id(), 14);
+    assert_eq!(ClanTag::new("#Q82U2QR9").unwrap().id(), 753_956_661);
+
+    // 双方登记结果一致
+    let a = ClanTag::new("#2PP").unwrap();
+    let b = ClanTag::new("#Q82U2QR9").unwrap();
+    assert!(a.is_first_mover(&b));
+    assert!(!b.is_first_mover(&a));
+    assert!(a.is_first_mover(&a));
+}
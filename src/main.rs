@@ -33,6 +33,9 @@ pub async fn run() {
         pool: database.get().await,
     };
 
+    // 定时任务
+    core::schedule::start(app_state.pool.clone());
+
     let address = format!("{}:{}", &server.get_path(), &server.get_port());
     let address = SocketAddr::from_str(&address).unwrap();
     log_info!("启动参数: {}", &address);
@@ -5,13 +5,15 @@ mod track;
 use crate::{
     AppState,
     api::{MiddleReadCompo, MiddleRoundApi, MiddleViewApi},
-    system::UserInfo,
+    core::tag::ClanTag,
+    safety::{ApiKey, ApiScope},
+    system::{AuditLog, UserInfo},
     util::RestApi,
 };
 use axum::{
     Json, Router,
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     routing::get,
 };
@@ -31,10 +33,10 @@ pub fn router() -> Router<AppState> {
 async fn track_tag(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    Path(tag): Path<String>,
+    Path(tag): Path<ClanTag>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("middle*track*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::MiddleTrackSelect).await {
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
@@ -45,7 +47,11 @@ async fn track_tag(
             if Utc::now() - r.update_time > chrono::Duration::hours(1) {
                 // 超过1h重新缓存
                 let mta = MiddleViewApi::get(&tag).await;
-                let cache = mta.clone().self_to_database().update(&app_state.pool).await;
+                let cache = mta
+                    .clone()
+                    .self_to_database(&tag)
+                    .update(&app_state.pool)
+                    .await;
                 if let Ok(r) = cache {
                     log_info!("Update Cache {}", r.rows_affected());
                     (StatusCode::OK, Json(mta))
@@ -63,7 +69,11 @@ async fn track_tag(
             log_warn!("Middle Error {}", e);
             // 第一次查询新增
             let mta = MiddleViewApi::get(&tag).await;
-            let cache = mta.clone().self_to_database().insert(&app_state.pool).await;
+            let cache = mta
+                .clone()
+                .self_to_database(&tag)
+                .insert(&app_state.pool)
+                .await;
             if let Ok(r) = cache {
                 log_info!("Create Cache {}", r.rows_affected());
                 (StatusCode::OK, Json(mta))
@@ -78,21 +88,26 @@ async fn track_tag(
 async fn round_insert(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     log_info!("User Token {}", token);
-    if !UserInfo::get_user(&token)
-        .await
-        .unwrap()
-        .check_role("admin")
-    {
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
         log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    match MiddleRoundApi::get().await.new_round(&app_state.pool).await {
-        Ok(res) => (StatusCode::OK, RestApi::successful(res)),
+    let middle_round = MiddleRoundApi::get().await;
+    match middle_round.new_round(&app_state.pool).await {
+        Ok(res) => {
+            AuditLog::new(&user_info, "insert", "round", &header_map)
+                .after(&middle_round)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(res))
+        }
         Err(e) => (StatusCode::GONE, RestApi::failed("Round Not Update", &e)),
     }
 }
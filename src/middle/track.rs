@@ -1,10 +1,13 @@
-use crate::api::{MiddleViewApi, MiddleViewApiDetails};
+use crate::{
+    api::{MiddleViewApi, MiddleViewApiDetails},
+    core::tag::ClanTag,
+};
 use chrono::{DateTime, Utc};
 use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, types::Json};
 use uuid::Uuid;
 use void_log::log_info;
 
-#[derive(Debug, Clone, PartialEq, Default, FromRow)]
+#[derive(Debug, Clone, PartialEq, FromRow)]
 pub struct Track {
     pub id: Uuid,
     pub server: String,
@@ -14,7 +17,7 @@ pub struct Track {
     pub summary: Vec<String>,
     pub create_time: DateTime<Utc>,
     pub update_time: DateTime<Utc>,
-    pub tag: String,
+    pub tag: ClanTag,
 }
 
 impl Track {
@@ -25,7 +28,7 @@ impl Track {
             public_total_score: self.public_total_score,
             details: self.details.0,
             summary: self.summary,
-            tag: self.tag,
+            tag: Some(self.tag),
         }
     }
 
@@ -33,8 +36,7 @@ impl Track {
         query_as("select * from middle.track").fetch_all(pool).await
     }
 
-    pub async fn select_tag(pool: &Pool<Postgres>, tag: &str) -> Result<Self, Error> {
-        let tag = format!("#{}", tag.replace("#", "").to_uppercase());
+    pub async fn select_tag(pool: &Pool<Postgres>, tag: &ClanTag) -> Result<Self, Error> {
         log_info!("Middle Search Tag {tag}");
         query_as("select * from middle.track where tag = $1")
             .bind(tag)
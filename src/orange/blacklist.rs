@@ -0,0 +1,222 @@
+use crate::orange::{Clan, ClanStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 黑名单记录
+/// 拉黑时保存部落原状态，解除或到期后恢复
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct Blacklist {
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+    pub clan_id: Uuid,
+    pub reason: String,
+    /// # 证据（截图链接等）
+    pub evidence: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub issue_by: Uuid,
+    #[serde(skip_deserializing)]
+    pub start_time: DateTime<Utc>,
+    /// # 到期时间，为空永久
+    pub expire_time: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub appeal_status: AppealStatus,
+    #[serde(skip_deserializing)]
+    pub appeal_text: Option<String>,
+    #[serde(skip_deserializing)]
+    pub appeal_reply: Option<String>,
+    #[serde(skip_deserializing)]
+    pub previous_status: ClanStatus,
+    #[serde(skip_deserializing)]
+    pub release_time: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub tag: Option<String>,
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum AppealStatus {
+    /// # 未申诉
+    #[default]
+    None = 0,
+    /// # 申诉中
+    Pending = 1,
+    /// # 申诉通过
+    Accepted = 2,
+    /// # 申诉驳回
+    Rejected = 3,
+}
+
+/// # 申诉审核
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct AppealReview {
+    pub accept: bool,
+    pub reply: Option<String>,
+}
+
+impl Display for Blacklist {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Blacklist {}\n - Clan: {} {:?} {:?}\n - Reason: {}\n - Start: {} | Expire: {:?} | Release: {:?}\n - Appeal: {:?}",
+            self.id,
+            self.clan_id,
+            self.tag,
+            self.name,
+            self.reason,
+            self.start_time,
+            self.expire_time,
+            self.release_time,
+            self.appeal_status
+        )
+    }
+}
+
+impl Blacklist {
+    pub fn is_active(&self) -> bool {
+        self.release_time.is_none()
+    }
+
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select b.*, c.tag, c.name from orange.blacklist b, orange.clan c where b.clan_id = c.id order by b.start_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select b.*, c.tag, c.name from orange.blacklist b, orange.clan c where b.clan_id = c.id and b.id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 部落当前黑名单
+    pub async fn select_clan(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Self, Error> {
+        query_as("select b.*, c.tag, c.name from orange.blacklist b, orange.clan c where b.clan_id = c.id and b.clan_id = $1 and b.release_time is null")
+            .bind(clan_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 拉黑
+    /// 部落状态改为黑名单，已在黑名单中返回RowNotFound
+    pub async fn insert(
+        &self,
+        pool: &Pool<Postgres>,
+        issue_by: Uuid,
+    ) -> Result<PgQueryResult, Error> {
+        if Self::select_clan(pool, self.clan_id).await.is_ok() {
+            return Err(Error::RowNotFound);
+        }
+        let clan = Clan::select(pool, self.clan_id).await?;
+        let res = query("insert into orange.blacklist values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, NULL, NULL, $8, NULL)")
+            .bind(self.clan_id)
+            .bind(&self.reason)
+            .bind(&self.evidence)
+            .bind(issue_by)
+            .bind(Utc::now())
+            .bind(self.expire_time)
+            .bind(AppealStatus::None)
+            .bind(clan.status.unwrap_or_default())
+            .execute(pool)
+            .await?;
+
+        let black_clan = Clan {
+            id: clan.id,
+            status: Some(ClanStatus::Blacklist),
+            ..Default::default()
+        };
+        black_clan.update_status(pool).await?;
+        log_info!("拉黑 {}", &clan);
+        Ok(res)
+    }
+
+    /// # 解除黑名单
+    /// 恢复部落原状态
+    pub async fn release(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let res = query(
+            "update orange.blacklist set release_time = $1 where id = $2 and release_time is null",
+        )
+        .bind(Utc::now())
+        .bind(self.id)
+        .execute(pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(Error::RowNotFound);
+        }
+
+        let clan = Clan {
+            id: Some(self.clan_id),
+            status: Some(self.previous_status),
+            ..Default::default()
+        };
+        clan.update_status(pool).await?;
+        log_info!("解除黑名单 {}", &self);
+        Ok(res)
+    }
+
+    /// # 提交申诉
+    pub async fn appeal(&self, pool: &Pool<Postgres>, text: &str) -> Result<PgQueryResult, Error> {
+        query("update orange.blacklist set appeal_status = $1, appeal_text = $2 where id = $3 and release_time is null")
+            .bind(AppealStatus::Pending)
+            .bind(text)
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 审核申诉
+    /// 通过即解除黑名单
+    pub async fn appeal_review(
+        &self,
+        pool: &Pool<Postgres>,
+        review: &AppealReview,
+    ) -> Result<PgQueryResult, Error> {
+        if self.appeal_status != AppealStatus::Pending {
+            return Err(Error::RowNotFound);
+        }
+        let status = if review.accept {
+            AppealStatus::Accepted
+        } else {
+            AppealStatus::Rejected
+        };
+        let res = query(
+            "update orange.blacklist set appeal_status = $1, appeal_reply = $2 where id = $3",
+        )
+        .bind(status)
+        .bind(&review.reply)
+        .bind(self.id)
+        .execute(pool)
+        .await?;
+        if review.accept {
+            self.release(pool).await?;
+        }
+        Ok(res)
+    }
+
+    /// # 到期自动解除
+    pub async fn expire_all(pool: &Pool<Postgres>) -> Result<usize, Error> {
+        let expired: Vec<Self> = query_as(
+            "select * from orange.blacklist where release_time is null and expire_time < $1",
+        )
+        .bind(Utc::now())
+        .fetch_all(pool)
+        .await?;
+        let mut count = 0;
+        for blacklist in expired {
+            match blacklist.release(pool).await {
+                Ok(_) => count += 1,
+                Err(e) => log_warn!("黑名单到期解除失败 {} {e}", blacklist.id),
+            }
+        }
+        Ok(count)
+    }
+}
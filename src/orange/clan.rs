@@ -1,22 +1,28 @@
 use crate::{
     api,
-    orange::clan_point::ClanPoint,
+    core::tag::ClanTag,
+    orange::{clan_history::ClanHistory, clan_point::ClanPoint},
     system::{User, UserInfo},
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use sqlx::{
-    Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as, query_scalar,
+    Error, FromRow, PgExecutor, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as,
+    query_scalar,
+};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    sync::{Mutex, OnceLock},
 };
-use std::fmt::{Display, Formatter};
 use uuid::Uuid;
 use void_log::log_info;
 
 #[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
 pub struct Clan {
     pub id: Option<Uuid>,
-    pub tag: Option<String>,
+    pub tag: Option<ClanTag>,
     pub name: Option<String>,
     #[serde(skip_deserializing)]
     pub create_time: DateTime<Utc>,
@@ -25,6 +31,54 @@ pub struct Clan {
     pub status: Option<ClanStatus>,
     pub series_id: Option<Uuid>,
     pub is_global: Option<bool>,
+    #[serde(skip_deserializing)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub deleted_by: Option<Uuid>,
+}
+
+/// # 部落搜索条件
+/// 文本同时匹配标签和名称，忽略大小写和重音，支持模糊匹配
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClanSearch {
+    pub text: Option<String>,
+    pub status: Option<ClanStatus>,
+    pub series_id: Option<Uuid>,
+    pub is_global: Option<bool>,
+    pub min_point: Option<i64>,
+    pub max_point: Option<i64>,
+    /// # 所在地区（最新快照）
+    pub location: Option<String>,
+    /// # 语言（最新快照）
+    pub chat_language: Option<String>,
+    pub sort: Option<SearchSort>,
+    pub desc: Option<bool>,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum SearchSort {
+    /// # 相似度
+    #[default]
+    Relevance = 0,
+    Name = 1,
+    Point = 2,
+    UpdateTime = 3,
+}
+
+/// # 搜索结果
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanSearchItem {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub clan: Clan,
+    pub point: Option<i64>,
+    pub location: Option<String>,
+    pub chat_language: Option<String>,
+    /// # 相似度，无搜索文本时为0
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
@@ -70,15 +124,80 @@ impl Display for ClanStatus {
     }
 }
 
+/// # 搜索语句
+/// 积分取当前积分，地区和语言取最新快照
+/// 排序组合有限，按语句缓存，每种只分配一次
+fn search_sql(select_text: &str, order_text: &str) -> &'static str {
+    static CACHE: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+    let sql = format!(
+        "select {select_text}
+        from orange.clan c
+        left join orange.clan_point p on p.clan_id = c.id
+        left join lateral (select location, chat_language from orange.clan_profile where clan_id = c.id order by snapshot_date desc limit 1) pr on true
+        where c.deleted_at is null
+        and ($1::text is null or unaccent(c.name) ilike unaccent('%' || $1 || '%') or c.tag ilike '%' || $1 || '%' or unaccent(lower(c.name)) % unaccent(lower($1)))
+        and ($2::smallint is null or c.status = $2)
+        and ($3::uuid is null or c.series_id = $3)
+        and ($4::bool is null or c.is_global = $4)
+        and ($5::bigint is null or p.point >= $5)
+        and ($6::bigint is null or p.point <= $6)
+        and ($7::text is null or pr.location ilike $7)
+        and ($8::text is null or pr.chat_language ilike $8)
+        {order_text}"
+    );
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = cache.get(&sql) {
+        return cached;
+    }
+    let leaked: &'static str = sql.clone().leak();
+    cache.insert(sql, leaked);
+    leaked
+}
+
+impl ClanSearch {
+    /// # 搜索文本
+    /// 空白视为不限
+    fn text(&self) -> Option<&str> {
+        self.text
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+    }
+
+    fn order_text(&self) -> String {
+        let column = match self.sort.unwrap_or_default() {
+            SearchSort::Relevance => "score",
+            SearchSort::Name => "lower(c.name)",
+            SearchSort::Point => "p.point",
+            SearchSort::UpdateTime => "c.update_time",
+        };
+        // 相似度默认降序，其余默认升序
+        let desc = self
+            .desc
+            .unwrap_or(self.sort.unwrap_or_default() == SearchSort::Relevance);
+        let direction = if desc {
+            "desc nulls last"
+        } else {
+            "asc nulls last"
+        };
+        format!("order by {column} {direction}, c.id limit $9 offset $10")
+    }
+}
+
 impl Clan {
     pub fn get_id(&self) -> Uuid {
         self.id.unwrap_or_default()
     }
 
     pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
-        query_as("select * from orange.clan where status >= 1 and status <= 3")
-            .fetch_all(pool)
-            .await
+        query_as(
+            "select * from orange.clan where status >= 1 and status <= 3 and deleted_at is null",
+        )
+        .fetch_all(pool)
+        .await
     }
 
     pub async fn select_page(
@@ -86,7 +205,7 @@ impl Clan {
         page: i64,
         page_size: i64,
     ) -> Result<Vec<Self>, Error> {
-        query_as("select * from orange.clan where status > 0 order by id limit $1 offset $2")
+        query_as("select * from orange.clan where status > 0 and deleted_at is null order by id limit $1 offset $2")
             .bind(page_size)
             .bind(page_size * (page - 1))
             .fetch_all(pool)
@@ -94,7 +213,7 @@ impl Clan {
     }
 
     pub async fn count(pool: &Pool<Postgres>) -> i64 {
-        query_scalar("select count(id) from orange.clan")
+        query_scalar("select count(id) from orange.clan where deleted_at is null")
             .fetch_one(pool)
             .await
             .unwrap_or_default()
@@ -107,28 +226,72 @@ impl Clan {
             .await
     }
 
+    /// # 标签查询
+    /// 当前标签优先，其次按历史标签找到现在的部落
     pub async fn select_tag(
         pool: &Pool<Postgres>,
-        tag: &str,
+        tag: &ClanTag,
         is_global: bool,
     ) -> Result<Self, Error> {
-        query_as("select * from orange.clan where tag = $1 and is_global = $2")
+        query_as("select * from orange.clan c where c.is_global = $2 and c.deleted_at is null and (c.tag = $1 or c.id in (select clan_id from orange.clan_history where tag = $1)) order by (c.tag = $1) desc limit 1")
             .bind(tag)
             .bind(is_global)
             .fetch_one(pool)
             .await
     }
 
-    pub async fn select_search(pool: &Pool<Postgres>, text: &str) -> Result<Vec<Self>, Error> {
-        let text = format!("%{text}%");
-        query_as("select * from orange.clan where tag like $1 or name like $1")
-            .bind(text)
+    /// # 需同步的部落
+    /// 外部、友盟部落不同步
+    pub async fn select_sync(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan where status in (1, 2, 4) and deleted_at is null")
             .fetch_all(pool)
             .await
     }
 
+    pub async fn select_search(
+        pool: &Pool<Postgres>,
+        search: &ClanSearch,
+    ) -> Result<Vec<ClanSearchItem>, Error> {
+        let page = search.page.unwrap_or(1).max(1);
+        let page_size = search.page_size.unwrap_or(20).clamp(1, 100);
+        let sql = search_sql(
+            "c.*, p.point, pr.location, pr.chat_language,
+            coalesce(greatest(similarity(unaccent(lower(c.name)), unaccent(lower($1))), similarity(lower(c.tag), lower($1))), 0)::real score",
+            &search.order_text(),
+        );
+        query_as(sql)
+            .bind(search.text())
+            .bind(search.status)
+            .bind(search.series_id)
+            .bind(search.is_global)
+            .bind(search.min_point)
+            .bind(search.max_point)
+            .bind(&search.location)
+            .bind(&search.chat_language)
+            .bind(page_size)
+            .bind(page_size * (page - 1))
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 搜索结果总数
+    pub async fn count_search(pool: &Pool<Postgres>, search: &ClanSearch) -> i64 {
+        query_scalar(search_sql("count(c.id)", ""))
+            .bind(search.text())
+            .bind(search.status)
+            .bind(search.series_id)
+            .bind(search.is_global)
+            .bind(search.min_point)
+            .bind(search.max_point)
+            .bind(&search.location)
+            .bind(&search.chat_language)
+            .fetch_one(pool)
+            .await
+            .unwrap_or_default()
+    }
+
     pub async fn clan_users(&self, pool: &Pool<Postgres>) -> Result<Vec<User>, Error> {
-        query_as("select * from public.user u, orange.clan_user cu where u.id = cu.user_id  and cu.clan_id = $1")
+        query_as("select * from public.user u, orange.clan_user cu where u.id = cu.user_id  and cu.clan_id = $1 and u.deleted_at is null")
             .bind(&self.id).fetch_all(pool).await
     }
 
@@ -144,46 +307,165 @@ impl Clan {
             .await
     }
 
+    /// # 新增并返回ID
+    /// 区分国际服/国服
+    pub async fn insert_returning(&self, executor: impl PgExecutor<'_>) -> Result<Uuid, Error> {
+        let now = Utc::now();
+        query_scalar(
+            "insert into orange.clan values(DEFAULT, $1, $2, $3, $3, $4, $5, $6) returning id",
+        )
+        .bind(&self.tag)
+        .bind(&self.name)
+        .bind(now)
+        .bind(&self.status)
+        .bind(&self.series_id)
+        .bind(self.is_global.unwrap_or(true))
+        .fetch_one(executor)
+        .await
+    }
+
+    /// # 更新部落
+    /// 标签或名称变化写入历史
     pub async fn update(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
-        query("update orange.clan set tag = $1, name = $2, update_time = $3, series_id = $4 where id = $5")
+        let before = Self::select(pool, self.get_id()).await;
+        let res = query("update orange.clan set tag = $1, name = $2, update_time = $3, series_id = $4 where id = $5")
             .bind(&self.tag)
             .bind(&self.name)
             .bind(now)
             .bind(&self.series_id)
             .bind(&self.id)
             .execute(pool)
-            .await
+            .await?;
+        if let Ok(before) = before {
+            ClanHistory::record(pool, &before, self).await?;
+        }
+        Ok(res)
     }
 
-    pub async fn update_status(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+    pub async fn update_name(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let now = Utc::now();
+        let before = Self::select(pool, self.get_id()).await;
+        let res = query("update orange.clan set name = $1, update_time = $2 where id = $3")
+            .bind(&self.name)
+            .bind(now)
+            .bind(&self.id)
+            .execute(pool)
+            .await?;
+        if let Ok(before) = before {
+            let after = Self {
+                name: self.name.clone(),
+                ..before.clone()
+            };
+            ClanHistory::record(pool, &before, &after).await?;
+        }
+        Ok(res)
+    }
+
+    pub async fn update_status(
+        &self,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
         query("update orange.clan set update_time = $1, status = $2 where id = $3")
             .bind(now)
             .bind(&self.status)
             .bind(&self.id)
+            .execute(executor)
+            .await
+    }
+
+    /// # 归档部落
+    pub async fn select_archived(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan where deleted_at is not null order by deleted_at desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 删除（归档）
+    /// 保留积分、成员和对局记录，可恢复
+    pub async fn delete(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        deleted_by: Uuid,
+    ) -> Result<PgQueryResult, Error> {
+        query("update orange.clan set deleted_at = $1, deleted_by = $2 where id = $3 and deleted_at is null")
+            .bind(Utc::now())
+            .bind(deleted_by)
+            .bind(id)
             .execute(pool)
             .await
     }
 
-    pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
-        ClanUser::delete_clan(id, pool).await?;
-        ClanPoint::delete(pool, id).await?;
-        query("delete from orange.clan where id = $1")
+    pub async fn restore(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        query("update orange.clan set deleted_at = NULL, deleted_by = NULL, update_time = $1 where id = $2 and deleted_at is not null")
+            .bind(Utc::now())
             .bind(id)
             .execute(pool)
             .await
     }
 
+    /// # 历史引用数
+    /// 对局、黑名单、入驻申请、奖惩、对战统计、出战提醒、申诉
+    pub async fn references(pool: &Pool<Postgres>, id: Uuid) -> i64 {
+        query_scalar(
+            "select (select count(*) from orange.track where self_clan_id = $1 or rival_clan_id = $1)
+            + (select count(*) from orange.blacklist where clan_id = $1)
+            + (select count(*) from orange.clan_apply where clan_id = $1)
+            + (select count(*) from orange.operate_log where clan_id = $1)
+            + (select count(*) from orange.war_stat where clan_id = $1)
+            + (select count(*) from orange.war_attack where clan_id = $1)
+            + (select count(*) from orange.player_alert where clan_id = $1)
+            + (select count(*) from orange.track_dispute where clan_id = $1)",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap_or_default()
+    }
+
+    /// # 彻底删除
+    /// 只删除已归档且无历史引用的部落，关联数据同一事务删除
+    pub async fn purge(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        let clan = Self::select(pool, id).await?;
+        if clan.deleted_at.is_none() || Self::references(pool, id).await > 0 {
+            return Err(Error::RowNotFound);
+        }
+        let mut tx = pool.begin().await?;
+        ClanUser::delete_clan(id, &mut *tx).await?;
+        ClanPoint::delete(&mut *tx, id).await?;
+        for sql in [
+            "delete from orange.clan_history where clan_id = $1",
+            "delete from orange.clan_profile where clan_id = $1",
+            "delete from orange.clan_roster where clan_id = $1",
+            "delete from orange.clan_compo where clan_id = $1",
+            "delete from orange.clan_lock where clan_id = $1",
+            "delete from orange.clan_verify where clan_id = $1",
+        ] {
+            query(sql).bind(id).execute(&mut *tx).await?;
+        }
+        let res = query("delete from orange.clan where id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        log_info!("彻底删除 {}", &clan);
+        Ok(res)
+    }
+
     /// # 接口自动更新
     pub async fn api_insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
-        let tag = &self.tag.clone().unwrap_or_default();
+        let Some(tag) = self.tag.as_ref() else {
+            return Err(Error::ColumnNotFound("tag".to_string()));
+        };
         let clan = api::Clan::get(tag).await.api_to_orange();
         clan.insert(pool).await
     }
 
     pub async fn api_update(&mut self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
-        let tag = &self.tag.clone().unwrap_or_default();
+        let Some(tag) = self.tag.as_ref() else {
+            return Err(Error::ColumnNotFound("tag".to_string()));
+        };
         let clan = api::Clan::get(tag).await.api_to_orange();
         self.tag = clan.tag;
         self.name = clan.name;
@@ -207,7 +489,7 @@ impl User {
     ///
     /// ```
     pub async fn user_clans(&self, pool: &Pool<Postgres>) -> Result<Vec<Clan>, Error> {
-        query_as("select c.* from orange.clan c, orange.clan_user cu where c.id = cu.clan_id and cu.user_id = $1 and c.status = 1")
+        query_as("select c.* from orange.clan c, orange.clan_user cu where c.id = cu.clan_id and cu.user_id = $1 and c.status = 1 and c.deleted_at is null")
             .bind(&self.id).fetch_all(pool).await
     }
 }
@@ -215,7 +497,7 @@ impl User {
 impl UserInfo {
     pub async fn user_clans(&self, pool: &Pool<Postgres>) -> Result<Vec<Clan>, Error> {
         log_info!("From Redis {}", &self.get_id());
-        query_as("select c.* from orange.clan c, orange.clan_user cu where c.id = cu.clan_id and cu.user_id = $1 and c.status = 1")
+        query_as("select c.* from orange.clan c, orange.clan_user cu where c.id = cu.clan_id and cu.user_id = $1 and c.status = 1 and c.deleted_at is null")
             .bind(&self.get_id()).fetch_all(pool).await
     }
 }
@@ -227,17 +509,19 @@ pub struct ClanUser {
 }
 
 impl ClanUser {
-    pub async fn select(&self, pool: &Pool<Postgres>) -> Result<Self, Error> {
+    pub async fn select(&self, executor: impl PgExecutor<'_>) -> Result<Self, Error> {
         query_as("select * from orange.clan_user where clan_id = $1 and user_id = $2")
-            .fetch_one(pool)
+            .bind(self.clan_id)
+            .bind(self.user_id)
+            .fetch_one(executor)
             .await
     }
 
-    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+    pub async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
         query("insert into orange.clan_user values ($1, $2)")
             .bind(self.clan_id)
             .bind(self.user_id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
@@ -256,20 +540,25 @@ impl ClanUser {
             .await
     }
 
-    pub async fn delete_clan(clan_id: Uuid, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+    pub async fn delete_clan(
+        clan_id: Uuid,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<PgQueryResult, Error> {
         query("delete from orange.clan_user where clan_id = $1")
             .bind(clan_id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 }
 
 impl api::Clan {
+    /// # 接口数据转部落
+    /// 新部落默认锁定，首领验证归属后转为正常
     pub fn api_to_orange(&self) -> Clan {
         Clan {
-            tag: (&self).tag.clone(),
+            tag: self.tag.as_deref().and_then(|t| ClanTag::new(t).ok()),
             name: (&self).name.clone(),
-            status: Some(ClanStatus::Ready),
+            status: Some(ClanStatus::Locked),
             ..Default::default()
         }
     }
@@ -0,0 +1,290 @@
+use crate::{
+    api,
+    core::tag::ClanTag,
+    orange::{Blacklist, Clan, ClanLock, ClanPoint, ClanStatus, ClanUser, clan_verify::ClanVerify},
+    system::User,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{
+    Error, FromRow, PgExecutor, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as,
+    types::Json,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 加盟申请
+/// 首领提交标签和联系方式，接口数据在提交时落库供审核参考
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct ClanApply {
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+    pub tag: ClanTag,
+    pub is_global: Option<bool>,
+    pub contact_name: String,
+    pub contact_email: Option<String>,
+    pub contact_phone: Option<String>,
+    /// # 首领账号（邮箱或编号）
+    pub user_code: Option<String>,
+    pub remarks: Option<String>,
+    #[serde(skip_deserializing)]
+    pub name: Option<String>,
+    #[serde(skip_deserializing)]
+    pub clan_level: Option<i64>,
+    #[serde(skip_deserializing)]
+    pub members: Option<i64>,
+    #[serde(skip_deserializing)]
+    pub is_war_log_public: Option<bool>,
+    #[serde(skip_deserializing)]
+    pub required_town_hall_level: Option<i64>,
+    #[serde(skip_deserializing)]
+    pub town_halls: Json<BTreeMap<i64, i64>>,
+    #[serde(skip_deserializing)]
+    pub status: ApplyStatus,
+    #[serde(skip_deserializing)]
+    pub reason: Option<String>,
+    #[serde(skip_deserializing)]
+    pub review_by: Option<Uuid>,
+    #[serde(skip_deserializing)]
+    pub review_time: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub create_time: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    pub clan_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum ApplyStatus {
+    /// # 待审核
+    #[default]
+    Pending = 0,
+    /// # 通过
+    Approved = 1,
+    /// # 拒绝
+    Rejected = 2,
+}
+
+/// # 审核
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ApplyReview {
+    pub approve: bool,
+    pub reason: Option<String>,
+}
+
+/// # 审核结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplyResponse {
+    /// # 通过（是否关联已验证首领）
+    Approved(bool),
+    Rejected,
+    NotPending,
+    ClanExists,
+    /// # 部落在黑名单或准入锁定中
+    ClanRestricted,
+    ReasonRequired,
+}
+
+impl Display for ClanApply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Apply {}\n - Tag: {} {:?}\n - Contact: {} {:?} {:?}\n - Level: {:?} | Members: {:?} | WarLog: {:?}\n - TownHalls: {:?}\n - Status: {:?}",
+            self.id,
+            self.tag,
+            self.name,
+            self.contact_name,
+            self.contact_email,
+            self.contact_phone,
+            self.clan_level,
+            self.members,
+            self.is_war_log_public,
+            self.town_halls.0,
+            self.status
+        )
+    }
+}
+
+impl ClanApply {
+    fn is_global(&self) -> bool {
+        self.is_global.unwrap_or(true)
+    }
+
+    /// # 拉取接口数据
+    /// 接口查无此部落返回false
+    pub async fn load_api(&mut self) -> bool {
+        let api_clan = api::Clan::get(&self.tag).await;
+        if api_clan.tag.is_none() {
+            log_warn!("申请部落不存在 {} {:?}", &self.tag, api_clan.reason);
+            return false;
+        }
+        self.name = api_clan.name.clone();
+        self.clan_level = api_clan.clan_level;
+        self.members = api_clan.members;
+        self.is_war_log_public = api_clan.is_war_log_public;
+        self.required_town_hall_level = api_clan.required_town_hall_level;
+        self.town_halls = Json(api_clan.info());
+        true
+    }
+
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan_apply order by create_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from orange.clan_apply where id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 同标签待审核申请
+    pub async fn select_pending(&self, pool: &Pool<Postgres>) -> Result<Self, Error> {
+        query_as("select * from orange.clan_apply where tag = $1 and status = 0")
+            .bind(&self.tag)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.clan_apply values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NULL, NULL, NULL, $15, NULL)")
+            .bind(&self.tag)
+            .bind(self.is_global())
+            .bind(&self.contact_name)
+            .bind(&self.contact_email)
+            .bind(&self.contact_phone)
+            .bind(&self.user_code)
+            .bind(&self.remarks)
+            .bind(&self.name)
+            .bind(self.clan_level)
+            .bind(self.members)
+            .bind(self.is_war_log_public)
+            .bind(self.required_town_hall_level)
+            .bind(&self.town_halls)
+            .bind(self.status)
+            .bind(Utc::now())
+            .execute(pool)
+            .await
+    }
+
+    async fn update_review(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
+        query("update orange.clan_apply set status = $1, reason = $2, review_by = $3, review_time = $4, clan_id = $5 where id = $6")
+            .bind(self.status)
+            .bind(&self.reason)
+            .bind(self.review_by)
+            .bind(self.review_time)
+            .bind(self.clan_id)
+            .bind(self.id)
+            .execute(executor)
+            .await
+    }
+
+    /// # 审核
+    /// 通过：新建正常部落、初始积分、关联已完成归属验证的首领
+    /// 首领未验证时下发归属验证码，验证通过后再关联
+    pub async fn review(
+        mut self,
+        pool: &Pool<Postgres>,
+        review: ApplyReview,
+        review_by: Uuid,
+    ) -> Result<ApplyResponse, Error> {
+        if self.status != ApplyStatus::Pending {
+            return Ok(ApplyResponse::NotPending);
+        }
+        self.reason = review.reason;
+        self.review_by = Some(review_by);
+        self.review_time = Some(Utc::now());
+
+        // 拒绝需说明原因
+        if !review.approve {
+            if self.reason.as_ref().is_none_or(|r| r.is_empty()) {
+                return Ok(ApplyResponse::ReasonRequired);
+            }
+            self.status = ApplyStatus::Rejected;
+            self.update_review(pool).await?;
+            return Ok(ApplyResponse::Rejected);
+        }
+
+        // 首领关联，申请表账号未经确认，须已完成本部落归属验证
+        let user_code = self
+            .user_code
+            .clone()
+            .or_else(|| self.contact_email.clone())
+            .unwrap_or_default();
+        let user_id = match User::select_code(pool, &user_code).await {
+            Ok(user) => user.id,
+            Err(_) => {
+                log_warn!("首领账号不存在 {}", &user_code);
+                None
+            }
+        };
+
+        // 已有部落（外部缓存可转正）
+        let mut tx = pool.begin().await?;
+        let clan_id = match Clan::select_tag(pool, &self.tag, self.is_global()).await {
+            Ok(clan) if clan.status.is_some_and(|s| s == ClanStatus::Ready) => {
+                return Ok(ApplyResponse::ClanExists);
+            }
+            Ok(clan)
+                if clan.status.is_some_and(|s| s == ClanStatus::Blacklist)
+                    || Blacklist::select_clan(pool, clan.get_id()).await.is_ok()
+                    || ClanLock::select_clan(pool, clan.get_id()).await.is_ok() =>
+            {
+                return Ok(ApplyResponse::ClanRestricted);
+            }
+            Ok(mut clan) => {
+                clan.status = Some(ClanStatus::Ready);
+                clan.update_status(&mut *tx).await?;
+                clan.get_id()
+            }
+            Err(_) => {
+                let clan = Clan {
+                    tag: Some(self.tag.clone()),
+                    name: self.name.clone(),
+                    status: Some(ClanStatus::Ready),
+                    is_global: Some(self.is_global()),
+                    ..Default::default()
+                };
+                clan.insert_returning(&mut *tx).await?
+            }
+        };
+
+        // 初始积分
+        if ClanPoint::select(&mut *tx, clan_id).await.is_err() {
+            let clan = Clan {
+                id: Some(clan_id),
+                ..Default::default()
+            };
+            clan.point_insert(&mut *tx).await?;
+        }
+
+        let mut linked = false;
+        if let Some(user_id) = user_id {
+            linked = ClanVerify::user_verified(pool, clan_id, user_id).await;
+            if linked {
+                let clan_user = ClanUser { clan_id, user_id };
+                if clan_user.select(&mut *tx).await.is_err() {
+                    clan_user.insert(&mut *tx).await?;
+                }
+            } else {
+                let clan_verify = ClanVerify::new(clan_id, user_id);
+                clan_verify.insert(&mut *tx).await?;
+                log_warn!("首领未完成归属验证，下发验证码 {}", &clan_verify);
+            }
+        }
+
+        self.status = ApplyStatus::Approved;
+        self.clan_id = Some(clan_id);
+        self.update_review(&mut *tx).await?;
+        tx.commit().await?;
+        log_info!("申请通过 {}", &self);
+        Ok(ApplyResponse::Approved(linked))
+    }
+}
@@ -0,0 +1,190 @@
+use crate::api::{self, MiddleReadCompo};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{
+    Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as, types::Json,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+};
+use uuid::Uuid;
+
+/// # 部落阵容快照
+/// 每轮每个部落一条，同步时覆盖为最新
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanCompo {
+    pub id: Uuid,
+    pub clan_id: Uuid,
+    pub round_id: Uuid,
+    pub town_halls: Json<BTreeMap<i64, i64>>,
+    pub members: i64,
+    /// # 按人数加权的平均本等级
+    pub th_avg: f32,
+    /// # 联盟本均区间，中间库不可用时为空
+    pub band_min: Option<f32>,
+    pub band_max: Option<f32>,
+    pub band_position: Option<BandPosition>,
+    pub update_time: DateTime<Utc>,
+    #[sqlx(default)]
+    pub tag: Option<String>,
+    #[sqlx(default)]
+    pub name: Option<String>,
+    #[sqlx(default)]
+    pub round_code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum BandPosition {
+    /// # 低于区间
+    Below = -1,
+    /// # 区间内
+    #[default]
+    Within = 0,
+    /// # 高于区间
+    Above = 1,
+}
+
+/// # 部落在联盟区间中的位置
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CompoPlacement {
+    #[serde(flatten)]
+    pub compo: ClanCompo,
+    /// # 与上一轮本均差值
+    pub th_avg_delta: Option<f32>,
+    /// # 近几轮快照，新的在前
+    pub trend: Vec<ClanCompo>,
+}
+
+impl Display for ClanCompo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Compo {} {}\n - TH Avg: {:.2} | Members: {}\n - Band: {:?}-{:?} {:?}\n - TownHalls: {:?}",
+            self.clan_id,
+            self.round_id,
+            self.th_avg,
+            self.members,
+            self.band_min,
+            self.band_max,
+            self.band_position,
+            self.town_halls.0
+        )
+    }
+}
+
+impl BandPosition {
+    pub fn of(th_avg: f32, min: f32, max: f32) -> Self {
+        if th_avg < min {
+            BandPosition::Below
+        } else if th_avg > max {
+            BandPosition::Above
+        } else {
+            BandPosition::Within
+        }
+    }
+}
+
+impl ClanCompo {
+    /// # 趋势轮数
+    const TREND_ROUNDS: i64 = 10;
+
+    /// # 同服务器中间库数据计算位置
+    pub fn new(
+        clan_id: Uuid,
+        round_id: Uuid,
+        api_clan: &api::Clan,
+        compo: Option<&MiddleReadCompo>,
+    ) -> Self {
+        let town_halls = api_clan.info();
+        let th_avg = api_clan.th_avg();
+        Self {
+            clan_id,
+            round_id,
+            members: town_halls.values().sum(),
+            town_halls: Json(town_halls),
+            th_avg,
+            band_min: compo.map(|c| c.get_min_th_avg()),
+            band_max: compo.map(|c| c.get_max_th_avg()),
+            band_position: compo
+                .map(|c| BandPosition::of(th_avg, c.get_min_th_avg(), c.get_max_th_avg())),
+            update_time: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    pub async fn upsert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query(
+            "insert into orange.clan_compo values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9)
+            on conflict (clan_id, round_id) do update set town_halls = $3, members = $4, th_avg = $5,
+            band_min = $6, band_max = $7, band_position = $8, update_time = $9",
+        )
+        .bind(self.clan_id)
+        .bind(self.round_id)
+        .bind(&self.town_halls)
+        .bind(self.members)
+        .bind(self.th_avg)
+        .bind(self.band_min)
+        .bind(self.band_max)
+        .bind(self.band_position)
+        .bind(self.update_time)
+        .execute(pool)
+        .await
+    }
+
+    /// # 部落历轮快照
+    pub async fn select_clan(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select cc.*, c.tag, c.name, r.code round_code from orange.clan_compo cc, orange.clan c, orange.round r where cc.clan_id = c.id and cc.round_id = r.id and cc.clan_id = $1 order by r.create_time desc")
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 本轮联盟阵容对比
+    /// 按本均从高到低，附近几轮趋势
+    pub async fn placement(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+    ) -> Result<Vec<CompoPlacement>, Error> {
+        let recent: Vec<Self> = query_as(
+            "select cc.*, c.tag, c.name, r.code round_code
+            from orange.clan_compo cc, orange.clan c, orange.round r
+            where cc.clan_id = c.id and cc.round_id = r.id and c.deleted_at is null
+            and cc.clan_id in (select clan_id from orange.clan_compo where round_id = $1)
+            and cc.round_id in (select id from orange.round where create_time <= (select create_time from orange.round where id = $1) order by create_time desc limit $2)
+            order by r.create_time desc",
+        )
+        .bind(round_id)
+        .bind(Self::TREND_ROUNDS)
+        .fetch_all(pool)
+        .await?;
+
+        let mut trends: HashMap<Uuid, Vec<Self>> = HashMap::new();
+        for compo in recent {
+            trends.entry(compo.clan_id).or_default().push(compo);
+        }
+        let mut placements: Vec<CompoPlacement> = trends
+            .into_values()
+            .filter_map(|trend| {
+                let compo = trend.first().filter(|c| c.round_id == round_id)?.clone();
+                let th_avg_delta = trend.get(1).map(|p| compo.th_avg - p.th_avg);
+                Some(CompoPlacement {
+                    compo,
+                    th_avg_delta,
+                    trend,
+                })
+            })
+            .collect();
+        placements.sort_by(|a, b| b.compo.th_avg.total_cmp(&a.compo.th_avg));
+        Ok(placements)
+    }
+}
+
+#[test]
+fn test_band_position() {
+    assert_eq!(BandPosition::of(14.2, 14.5, 15.5), BandPosition::Below);
+    assert_eq!(BandPosition::of(14.5, 14.5, 15.5), BandPosition::Within);
+    assert_eq!(BandPosition::of(15.6, 14.5, 15.5), BandPosition::Above);
+}
@@ -0,0 +1,106 @@
+use crate::{core::tag::ClanTag, orange::Clan};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::log_info;
+
+/// # 部落标签/名称历史
+/// 每条记录一个有效区间，`end_time` 为空表示当前
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanHistory {
+    pub id: Uuid,
+    pub clan_id: Uuid,
+    pub tag: Option<ClanTag>,
+    pub name: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+impl Display for ClanHistory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan History {}\n - Tag: {:?} | Name: {:?}\n - {} -> {:?}",
+            self.clan_id, self.tag, self.name, self.start_time, self.end_time
+        )
+    }
+}
+
+/// # 对局时的部落字段
+/// 按对局时间取历史记录，没有历史时用当前值
+pub fn history_at(clan: &str, time: &str, column: &str) -> String {
+    format!(
+        "coalesce((select h.{column} from orange.clan_history h where h.clan_id = {clan}.id and h.start_time <= {time} and (h.end_time is null or h.end_time > {time}) order by h.start_time desc limit 1), {clan}.{column})"
+    )
+}
+
+impl ClanHistory {
+    pub async fn select_clan(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan_history where clan_id = $1 order by start_time desc")
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn has_current(pool: &Pool<Postgres>, clan_id: Uuid) -> bool {
+        query_as::<_, Self>(
+            "select * from orange.clan_history where clan_id = $1 and end_time is null",
+        )
+        .bind(clan_id)
+        .fetch_one(pool)
+        .await
+        .is_ok()
+    }
+
+    async fn insert(
+        pool: &Pool<Postgres>,
+        clan: &Clan,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<PgQueryResult, Error> {
+        query("insert into orange.clan_history values(DEFAULT, $1, $2, $3, $4, $5)")
+            .bind(clan.get_id())
+            .bind(&clan.tag)
+            .bind(&clan.name)
+            .bind(start_time)
+            .bind(end_time)
+            .execute(pool)
+            .await
+    }
+
+    /// # 记录变更
+    /// 标签或名称变化时结束当前区间并新开一条；
+    /// 旧数据没有历史时先按创建时间补一条
+    pub async fn record(pool: &Pool<Postgres>, before: &Clan, after: &Clan) -> Result<(), Error> {
+        if before.tag == after.tag && before.name == after.name {
+            return Ok(());
+        }
+        let now = Utc::now();
+        if Self::has_current(pool, before.get_id()).await {
+            query("update orange.clan_history set end_time = $1 where clan_id = $2 and end_time is null")
+                .bind(now)
+                .bind(before.get_id())
+                .execute(pool)
+                .await?;
+        } else {
+            Self::insert(pool, before, before.create_time, Some(now)).await?;
+        }
+        let current = Clan {
+            id: before.id,
+            tag: after.tag.clone(),
+            name: after.name.clone(),
+            ..Default::default()
+        };
+        Self::insert(pool, &current, now, None).await?;
+        log_info!(
+            "部落变更 {:?} {:?} -> {:?} {:?}",
+            before.tag,
+            before.name,
+            after.tag,
+            after.name
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::orange::Clan;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as};
+use sqlx::{Error, FromRow, PgExecutor, Pool, Postgres, postgres::PgQueryResult, query, query_as};
 use std::fmt::{Display, Formatter};
 use uuid::Uuid;
 use void_log::log_warn;
@@ -40,15 +40,15 @@ impl ClanPoint {
     }
 
     pub async fn select_all(pool: &Pool<Postgres>) -> Result<Self, Error> {
-        query_as("select oc.tag, oc.name, ocp.* from orange.clan oc, orange.clan_point ocp where oc.id = ocp.clan_id")
+        query_as("select oc.tag, oc.name, ocp.* from orange.clan oc, orange.clan_point ocp where oc.id = ocp.clan_id and oc.deleted_at is null")
             .fetch_one(pool)
             .await
     }
 
-    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+    pub async fn select(executor: impl PgExecutor<'_>, id: Uuid) -> Result<Self, Error> {
         query_as("select oc.tag, oc.name, ocp.* from orange.clan oc, orange.clan_point ocp where oc.id = ocp.clan_id and ocp.clan_id = $1")
             .bind(id)
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await
     }
 
@@ -67,7 +67,7 @@ impl ClanPoint {
     /// 登记异常：配合取消登记使用
     /// 传入修正的积分
     pub async fn repair_point(
-        pool: &Pool<Postgres>,
+        executor: impl PgExecutor<'_>,
         clan_id: Uuid,
         point: i64,
     ) -> Result<PgQueryResult, Error> {
@@ -76,7 +76,7 @@ impl ClanPoint {
             .bind(point)
             .bind(now)
             .bind(clan_id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
@@ -85,7 +85,7 @@ impl ClanPoint {
     /// 改分：+add
     pub async fn update_point(
         &self,
-        pool: &Pool<Postgres>,
+        executor: impl PgExecutor<'_>,
         add: i64,
     ) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
@@ -93,22 +93,28 @@ impl ClanPoint {
             .bind(&self.point + add)
             .bind(now)
             .bind(&self.clan_id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
+    /// # 实际变动的奖励券
+    /// 超过5张不再变动
+    pub fn reward_add(&self, reward_add: i64) -> i64 {
+        if self.reward_point > 5 { 0 } else { reward_add }
+    }
+
     pub async fn update_reward_point(
         &self,
-        pool: &Pool<Postgres>,
+        executor: impl PgExecutor<'_>,
         reward_add: i64,
     ) -> Result<PgQueryResult, Error> {
-        let reward_add = if self.reward_point > 5 { 0 } else { reward_add };
-        self.update_reward_point_base(pool, reward_add).await
+        self.update_reward_point_base(executor, self.reward_add(reward_add))
+            .await
     }
 
     pub async fn update_reward_point_base(
         &self,
-        pool: &Pool<Postgres>,
+        executor: impl PgExecutor<'_>,
         reward_add: i64,
     ) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
@@ -116,14 +122,14 @@ impl ClanPoint {
             .bind(reward_add)
             .bind(now)
             .bind(&self.clan_id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
-    pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+    pub async fn delete(executor: impl PgExecutor<'_>, id: Uuid) -> Result<PgQueryResult, Error> {
         query("delete from orange.clan_point where clan_id = $1")
             .bind(id)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
@@ -145,13 +151,16 @@ impl Clan {
             .await
     }
 
-    pub async fn point_insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+    pub async fn point_insert(
+        &self,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<PgQueryResult, Error> {
         query("insert into orange.clan_point values($1, $2, $3, $3, $4)")
             .bind(self.id)
             .bind(0)
             .bind(Utc::now())
             .bind(1)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 }
@@ -0,0 +1,165 @@
+use crate::{
+    api::{self, MiddleReadCompo},
+    orange::{Clan, Round, clan_compo::ClanCompo, clan_roster::ClanRoster},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, types::Json};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 部落每日快照
+/// 定时任务从接口刷新，每个部落每天一条
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanProfile {
+    pub id: Uuid,
+    pub clan_id: Uuid,
+    pub snapshot_date: NaiveDate,
+    pub name: Option<String>,
+    pub clan_level: Option<i64>,
+    pub members: Option<i64>,
+    pub war_wins: Option<i64>,
+    pub war_losses: Option<i64>,
+    pub war_ties: Option<i64>,
+    pub war_win_streak: Option<i64>,
+    pub war_league: Option<String>,
+    pub is_war_log_public: Option<bool>,
+    pub town_halls: Json<BTreeMap<i64, i64>>,
+    pub location: Option<String>,
+    pub chat_language: Option<String>,
+    pub update_time: DateTime<Utc>,
+}
+
+impl Display for ClanProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Profile {} {}\n - Name: {:?}\n - Level: {:?} | Members: {:?}\n - War: {:?}/{:?}/{:?} Streak {:?} League {:?}\n - TownHalls: {:?}",
+            self.clan_id,
+            self.snapshot_date,
+            self.name,
+            self.clan_level,
+            self.members,
+            self.war_wins,
+            self.war_losses,
+            self.war_ties,
+            self.war_win_streak,
+            self.war_league,
+            self.town_halls.0
+        )
+    }
+}
+
+impl ClanProfile {
+    pub fn new(clan_id: Uuid, api_clan: &api::Clan) -> Self {
+        Self {
+            clan_id,
+            snapshot_date: Utc::now().date_naive(),
+            name: api_clan.name.clone(),
+            clan_level: api_clan.clan_level,
+            members: api_clan.members,
+            war_wins: api_clan.war_wins,
+            war_losses: api_clan.war_losses,
+            war_ties: api_clan.war_ties,
+            war_win_streak: api_clan.war_win_streak,
+            war_league: api_clan.war_league.as_ref().and_then(|l| l.name.clone()),
+            is_war_log_public: api_clan.is_war_log_public,
+            town_halls: Json(api_clan.info()),
+            location: api_clan.location.as_ref().and_then(|l| l.name.clone()),
+            chat_language: api_clan.chat_language.as_ref().and_then(|l| l.name.clone()),
+            update_time: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    /// # 部落历史快照
+    pub async fn select_clan(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan_profile where clan_id = $1 order by snapshot_date desc")
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 当日快照写入
+    /// 同一天重复同步覆盖
+    pub async fn upsert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query(
+            "insert into orange.clan_profile values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+            on conflict (clan_id, snapshot_date) do update set name = $3, clan_level = $4, members = $5,
+            war_wins = $6, war_losses = $7, war_ties = $8, war_win_streak = $9, war_league = $10,
+            is_war_log_public = $11, town_halls = $12, location = $13, chat_language = $14, update_time = $15",
+        )
+        .bind(self.clan_id)
+        .bind(self.snapshot_date)
+        .bind(&self.name)
+        .bind(self.clan_level)
+        .bind(self.members)
+        .bind(self.war_wins)
+        .bind(self.war_losses)
+        .bind(self.war_ties)
+        .bind(self.war_win_streak)
+        .bind(&self.war_league)
+        .bind(self.is_war_log_public)
+        .bind(&self.town_halls)
+        .bind(&self.location)
+        .bind(&self.chat_language)
+        .bind(self.update_time)
+        .execute(pool)
+        .await
+    }
+
+    /// # 同步全部部落
+    /// 刷新名称并写入当日快照、本轮成员和阵容，接口查不到的部落跳过
+    pub async fn sync(pool: &Pool<Postgres>) -> Result<usize, Error> {
+        let clans = Clan::select_sync(pool).await?;
+        let global_round = Round::select_last(pool, true).await.ok();
+        let china_round = Round::select_last(pool, false).await.ok();
+        let compo = match MiddleReadCompo::try_get().await {
+            Ok(compo) => Some(compo),
+            Err(e) => {
+                log_warn!("ReadCompo {e}");
+                None
+            }
+        };
+        let mut count = 0;
+        for mut clan in clans {
+            let Some(tag) = clan.tag.as_ref() else {
+                log_warn!("部落无标签 {}", clan.get_id());
+                continue;
+            };
+            let api_clan = api::Clan::get(tag).await;
+            tokio::time::sleep(api::API_INTERVAL).await;
+            if api_clan.tag.is_none() {
+                log_warn!("同步失败 {:?} {:?}", clan.tag, api_clan.reason);
+                continue;
+            }
+
+            if api_clan.name.is_some() && api_clan.name != clan.name {
+                log_info!("部落改名 {:?} -> {:?}", clan.name, api_clan.name);
+                clan.name = api_clan.name.clone();
+                clan.update_name(pool).await?;
+            }
+            Self::new(clan.get_id(), &api_clan).upsert(pool).await?;
+            let is_global = clan.is_global.unwrap_or(true);
+            let round = if is_global {
+                &global_round
+            } else {
+                &china_round
+            };
+            if let Some(round) = round {
+                ClanRoster::record(pool, round.get_id(), clan.get_id(), &api_clan).await?;
+                let server_compo = compo.as_ref().filter(|c| c.is_global() == is_global);
+                ClanCompo::new(clan.get_id(), round.get_id(), &api_clan, server_compo)
+                    .upsert(pool)
+                    .await?;
+            }
+            count += 1;
+        }
+        log_info!("部落同步完成 {count}");
+        Ok(count)
+    }
+}
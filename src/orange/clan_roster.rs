@@ -0,0 +1,228 @@
+use crate::{api, core::tag::PlayerTag, orange::Round};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::log_info;
+
+/// # 轮次成员快照
+/// 每轮每个部落每名成员一条，记录本轮首次和最后出现时间
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct ClanRoster {
+    pub id: Uuid,
+    pub round_id: Uuid,
+    pub clan_id: Uuid,
+    pub player_tag: PlayerTag,
+    pub player_name: Option<String>,
+    pub town_hall_level: Option<i64>,
+    pub role: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum HopKind {
+    /// # 同轮出现在多个部落
+    #[default]
+    MultiClan = 1,
+    /// # 开战前临时加入
+    LateJoin = 2,
+}
+
+/// # 可疑成员
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct RosterFlag {
+    pub kind: HopKind,
+    pub player_tag: PlayerTag,
+    pub player_name: Option<String>,
+    pub clan_ids: Vec<Uuid>,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// # 部落成员变动
+/// 与上一轮相比
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct RosterChurn {
+    pub clan_id: Uuid,
+    pub tag: Option<String>,
+    pub name: Option<String>,
+    pub members: i64,
+    pub joined: i64,
+    pub left: i64,
+}
+
+/// # 轮次成员报告
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RosterReport {
+    pub round_id: Uuid,
+    pub previous_round_id: Option<Uuid>,
+    pub churn: Vec<RosterChurn>,
+    pub flags: Vec<RosterFlag>,
+}
+
+impl Display for ClanRoster {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Roster {} {}\n - Player: {} {:?} TH{:?}\n - Seen: {} -> {}",
+            self.round_id,
+            self.clan_id,
+            self.player_tag,
+            self.player_name,
+            self.town_hall_level,
+            self.first_seen,
+            self.last_seen
+        )
+    }
+}
+
+impl ClanRoster {
+    /// # 开战前多久加入算临时加入
+    const LATE_JOIN: Duration = Duration::hours(24);
+
+    /// # 部落本轮成员
+    pub async fn select_clan(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+        clan_id: Uuid,
+    ) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan_roster where round_id = $1 and clan_id = $2 order by town_hall_level desc nulls last")
+            .bind(round_id)
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn upsert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query(
+            "insert into orange.clan_roster values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $7)
+            on conflict (round_id, clan_id, player_tag) do update set player_name = $4, town_hall_level = $5, role = $6, last_seen = $7",
+        )
+        .bind(self.round_id)
+        .bind(self.clan_id)
+        .bind(&self.player_tag)
+        .bind(&self.player_name)
+        .bind(self.town_hall_level)
+        .bind(&self.role)
+        .bind(self.last_seen)
+        .execute(pool)
+        .await
+    }
+
+    /// # 记录接口成员列表
+    pub async fn record(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+        clan_id: Uuid,
+        api_clan: &api::Clan,
+    ) -> Result<usize, Error> {
+        let now = Utc::now();
+        let mut count = 0;
+        for member in api_clan.member_list.iter().flatten() {
+            let Some(player_tag) = member.tag.as_deref().and_then(|t| PlayerTag::new(t).ok())
+            else {
+                continue;
+            };
+            let roster = Self {
+                round_id,
+                clan_id,
+                player_tag,
+                player_name: member.name.clone(),
+                town_hall_level: member.town_hall_level,
+                role: member.role.clone(),
+                id: Uuid::default(),
+                first_seen: now,
+                last_seen: now,
+            };
+            roster.upsert(pool).await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// # 成员变动
+    async fn churn(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+        previous_id: Uuid,
+    ) -> Result<Vec<RosterChurn>, Error> {
+        query_as(
+            "select r.clan_id, c.tag, c.name, count(*) members,
+            count(*) filter (where not exists (select 1 from orange.clan_roster p where p.round_id = $2 and p.clan_id = r.clan_id and p.player_tag = r.player_tag)) joined,
+            (select count(*) from orange.clan_roster p where p.round_id = $2 and p.clan_id = r.clan_id
+                and not exists (select 1 from orange.clan_roster n where n.round_id = $1 and n.clan_id = p.clan_id and n.player_tag = p.player_tag)) \"left\"
+            from orange.clan_roster r, orange.clan c
+            where r.clan_id = c.id and r.round_id = $1
+            group by r.clan_id, c.tag, c.name
+            order by joined desc",
+        )
+        .bind(round_id)
+        .bind(previous_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// # 同轮多部落
+    async fn multi_clan(pool: &Pool<Postgres>, round_id: Uuid) -> Result<Vec<RosterFlag>, Error> {
+        query_as(
+            "select 1::smallint kind, player_tag, max(player_name) player_name,
+            array_agg(distinct clan_id) clan_ids, min(first_seen) first_seen
+            from orange.clan_roster where round_id = $1
+            group by player_tag having count(distinct clan_id) > 1",
+        )
+        .bind(round_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// # 开战前临时加入
+    /// 上一轮已有该部落快照但不含此成员，且本轮首次出现在开战前限定时间内
+    async fn late_join(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+        previous_id: Uuid,
+        round_time: DateTime<Utc>,
+    ) -> Result<Vec<RosterFlag>, Error> {
+        query_as(
+            "select 2::smallint kind, r.player_tag, r.player_name, array[r.clan_id] clan_ids, r.first_seen
+            from orange.clan_roster r
+            where r.round_id = $1 and r.first_seen >= $3 and r.first_seen <= $4
+            and exists (select 1 from orange.clan_roster p where p.round_id = $2 and p.clan_id = r.clan_id)
+            and not exists (select 1 from orange.clan_roster p where p.round_id = $2 and p.clan_id = r.clan_id and p.player_tag = r.player_tag)
+            order by r.first_seen desc",
+        )
+        .bind(round_id)
+        .bind(previous_id)
+        .bind(round_time - Self::LATE_JOIN)
+        .bind(round_time)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// # 轮次报告
+    pub async fn report(pool: &Pool<Postgres>, round: &Round) -> Result<RosterReport, Error> {
+        let previous = Round::select_previous(pool, round).await.ok();
+        let previous_id = previous.as_ref().map(|r| r.get_id()).unwrap_or_default();
+
+        let mut flags = Self::multi_clan(pool, round.get_id()).await?;
+        flags.extend(
+            Self::late_join(pool, round.get_id(), previous_id, round.get_round_time()).await?,
+        );
+        let report = RosterReport {
+            round_id: round.get_id(),
+            previous_round_id: previous.map(|r| r.get_id()),
+            churn: Self::churn(pool, round.get_id(), previous_id).await?,
+            flags,
+        };
+        log_info!(
+            "成员报告 {} 变动部落 {} 可疑成员 {}",
+            round.get_code(),
+            report.churn.len(),
+            report.flags.len()
+        );
+        Ok(report)
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    api,
+    core::tag::ClanTag,
+    orange::{Blacklist, Clan, ClanLock, ClanStatus, ClanUser},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{
+    Error, FromRow, PgExecutor, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 部落归属验证
+/// 系统下发验证码，首领写入游戏内部落简介后提交校验
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanVerify {
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+    #[serde(skip_deserializing)]
+    pub clan_id: Uuid,
+    #[serde(skip_deserializing)]
+    pub user_id: Uuid,
+    #[serde(skip_deserializing)]
+    pub code: String,
+    #[serde(skip_deserializing)]
+    pub status: VerifyStatus,
+    #[serde(skip_deserializing)]
+    pub create_time: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    pub expire_time: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    pub verify_time: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    pub tag: Option<String>,
+    #[sqlx(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum VerifyStatus {
+    /// # 待验证
+    #[default]
+    Pending = 0,
+    /// # 已验证
+    Verified = 1,
+    /// # 已过期
+    Expired = 2,
+}
+
+/// # 申请验证
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClanVerifyNew {
+    pub tag: ClanTag,
+    pub is_global: Option<bool>,
+}
+
+/// # 验证结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyResponse {
+    Verified,
+    NotOwner,
+    Expired,
+    CodeNotFound,
+}
+
+impl Display for ClanVerify {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Verify {}\n - Clan: {} {:?}\n - User: {}\n - Code: {}\n - Status: {:?}",
+            self.id, self.clan_id, self.tag, self.user_id, self.code, self.status
+        )
+    }
+}
+
+impl ClanVerify {
+    /// # 验证码有效期
+    const EXPIRE_HOURS: i64 = 24;
+
+    pub fn new(clan_id: Uuid, user_id: Uuid) -> Self {
+        let now = Utc::now();
+        let code = Uuid::new_v4().simple().to_string()[..6].to_uppercase();
+        Self {
+            clan_id,
+            user_id,
+            code: format!("OI-{code}"),
+            create_time: now,
+            expire_time: now + Duration::hours(Self::EXPIRE_HOURS),
+            ..Default::default()
+        }
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select v.*, c.tag, c.name from orange.clan_verify v, orange.clan c where v.clan_id = c.id and v.id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn select_user(pool: &Pool<Postgres>, user_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select v.*, c.tag, c.name from orange.clan_verify v, orange.clan c where v.clan_id = c.id and v.user_id = $1 order by v.create_time desc")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 部落是否验证过
+    async fn clan_verified(pool: &Pool<Postgres>, clan_id: Uuid) -> bool {
+        query_as::<_, Self>("select * from orange.clan_verify where clan_id = $1 and status = 1")
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+            .is_ok_and(|v| !v.is_empty())
+    }
+
+    /// # 用户是否已验证该部落
+    pub async fn user_verified(pool: &Pool<Postgres>, clan_id: Uuid, user_id: Uuid) -> bool {
+        query_as::<_, Self>(
+            "select * from orange.clan_verify where clan_id = $1 and user_id = $2 and status = 1",
+        )
+        .bind(clan_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .is_ok_and(|v| !v.is_empty())
+    }
+
+    pub async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.clan_verify values(DEFAULT, $1, $2, $3, $4, $5, $6, NULL)")
+            .bind(self.clan_id)
+            .bind(self.user_id)
+            .bind(&self.code)
+            .bind(self.status)
+            .bind(self.create_time)
+            .bind(self.expire_time)
+            .execute(executor)
+            .await
+    }
+
+    async fn update_status(
+        &self,
+        pool: &Pool<Postgres>,
+        status: VerifyStatus,
+    ) -> Result<PgQueryResult, Error> {
+        let verify_time = if status == VerifyStatus::Verified {
+            Some(Utc::now())
+        } else {
+            None
+        };
+        query("update orange.clan_verify set status = $1, verify_time = $2 where id = $3")
+            .bind(status)
+            .bind(verify_time)
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 校验部落简介
+    /// 通过后激活首领关联，首次验证的锁定部落转为正常
+    /// 仍有准入锁定或黑名单时不改状态，由准入检查解锁
+    pub async fn verify(
+        &self,
+        pool: &Pool<Postgres>,
+        user_id: Uuid,
+    ) -> Result<VerifyResponse, Error> {
+        if self.user_id != user_id {
+            return Ok(VerifyResponse::NotOwner);
+        }
+        if self.status == VerifyStatus::Verified {
+            return Ok(VerifyResponse::Verified);
+        }
+        if self.status == VerifyStatus::Expired || self.expire_time < Utc::now() {
+            self.update_status(pool, VerifyStatus::Expired).await?;
+            return Ok(VerifyResponse::Expired);
+        }
+
+        let clan = Clan::select(pool, self.clan_id).await?;
+        let Some(tag) = clan.tag.as_ref() else {
+            log_warn!("部落无标签 {}", &clan);
+            return Ok(VerifyResponse::CodeNotFound);
+        };
+        let api_clan = api::Clan::get(tag).await;
+        let description = api_clan.description.unwrap_or_default();
+        log_info!("校验简介 {} | {}", &self.code, &description);
+        if !description.contains(&self.code) {
+            log_warn!("简介未找到验证码 {}", &self.code);
+            return Ok(VerifyResponse::CodeNotFound);
+        }
+
+        // 首次验证激活部落
+        let first_verify = !Self::clan_verified(pool, self.clan_id).await;
+        self.update_status(pool, VerifyStatus::Verified).await?;
+        let restricted = ClanLock::select_clan(pool, self.clan_id).await.is_ok()
+            || Blacklist::select_clan(pool, self.clan_id).await.is_ok();
+        if first_verify && !restricted && clan.status.is_some_and(|s| s == ClanStatus::Locked) {
+            let ready = Clan {
+                id: clan.id,
+                status: Some(ClanStatus::Ready),
+                ..Default::default()
+            };
+            ready.update_status(pool).await?;
+            log_info!("部落激活 {}", &clan);
+        }
+
+        // 激活首领关联
+        let clan_user = ClanUser {
+            clan_id: self.clan_id,
+            user_id: self.user_id,
+        };
+        if clan_user.select(pool).await.is_err() {
+            clan_user.insert(pool).await?;
+        }
+        Ok(VerifyResponse::Verified)
+    }
+}
@@ -0,0 +1,353 @@
+use crate::{
+    core::registration::{self, ReverseError},
+    orange::{
+        Track,
+        operate_log::{OperateLog, RewardType},
+    },
+    system::UserInfo,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{
+    Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as, query_scalar,
+    types::Json,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::log_info;
+
+/// # 对局申诉
+/// 对局任一方首领发起，另一方可回应，管理员裁定
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct TrackDispute {
+    pub id: Uuid,
+    pub track_id: Uuid,
+    /// # 发起方部落
+    pub clan_id: Uuid,
+    pub open_by: Uuid,
+    pub text: String,
+    /// # 附件地址
+    pub attachments: Json<Vec<String>>,
+    pub response: Option<String>,
+    pub response_attachments: Option<Json<Vec<String>>>,
+    pub respond_by: Option<Uuid>,
+    pub respond_time: Option<DateTime<Utc>>,
+    pub status: DisputeStatus,
+    pub resolution: Option<DisputeResolution>,
+    pub resolve_note: Option<String>,
+    pub resolve_by: Option<Uuid>,
+    pub resolve_time: Option<DateTime<Utc>>,
+    pub create_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum DisputeStatus {
+    /// # 待回应
+    #[default]
+    Open = 0,
+    /// # 对方已回应
+    Responded = 1,
+    /// # 已裁定
+    Resolved = 2,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum DisputeResolution {
+    /// # 维持原判
+    #[default]
+    Upheld = 1,
+    /// # 逆转结果
+    Reversed = 2,
+    /// # 处罚
+    Penalty = 3,
+}
+
+/// # 发起申诉
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DisputeNew {
+    pub track_id: Uuid,
+    pub text: String,
+    pub attachments: Option<Vec<String>>,
+}
+
+/// # 回应申诉
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DisputeRespond {
+    pub text: String,
+    pub attachments: Option<Vec<String>>,
+}
+
+/// # 裁定申诉
+/// 处罚时须指定被处罚部落，默认处罚1
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DisputeResolve {
+    pub resolution: DisputeResolution,
+    pub note: Option<String>,
+    pub penalty_clan_id: Option<Uuid>,
+    pub reward_type: Option<RewardType>,
+}
+
+#[derive(Debug)]
+pub enum DisputeError {
+    EmptyText,
+    /// # 不是对局双方部落的成员
+    NotInvolved,
+    /// # 该对局已有未裁定的申诉
+    AlreadyOpen,
+    /// # 发起方不能回应自己的申诉
+    SameSide,
+    Resolved,
+    InvalidPenalty,
+    /// # 逆转失败，仅内部匹配可逆转
+    ReverseFailed,
+    /// # 该对局已逆转
+    AlreadyReversed,
+    Database(Error),
+}
+
+impl Display for TrackDispute {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Track Dispute {} {}\n - Clan: {} | Status: {:?} {:?}\n - Text: {}\n - Response: {:?}",
+            self.id,
+            self.track_id,
+            self.clan_id,
+            self.status,
+            self.resolution,
+            self.text,
+            self.response
+        )
+    }
+}
+
+impl Display for DisputeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg_en())
+    }
+}
+
+impl From<Error> for DisputeError {
+    fn from(value: Error) -> Self {
+        DisputeError::Database(value)
+    }
+}
+
+impl From<ReverseError> for DisputeError {
+    fn from(value: ReverseError) -> Self {
+        match value {
+            ReverseError::NotInternal => DisputeError::ReverseFailed,
+            ReverseError::AlreadyReversed => DisputeError::AlreadyReversed,
+            ReverseError::Database(e) => DisputeError::Database(e),
+        }
+    }
+}
+
+impl DisputeError {
+    pub fn msg_en(&self) -> String {
+        match self {
+            DisputeError::EmptyText => "Text is required".to_string(),
+            DisputeError::NotInvolved => "Not a member of either clan".to_string(),
+            DisputeError::AlreadyOpen => "Track already has an open dispute".to_string(),
+            DisputeError::SameSide => "Only the other clan can respond".to_string(),
+            DisputeError::Resolved => "Dispute already resolved".to_string(),
+            DisputeError::InvalidPenalty => {
+                "Penalty needs a clan of this track and a penalty type".to_string()
+            }
+            DisputeError::ReverseFailed => "Only internal tracks can be reversed".to_string(),
+            DisputeError::AlreadyReversed => "Track already reversed".to_string(),
+            DisputeError::Database(e) => format!("Database error: {e}"),
+        }
+    }
+
+    pub fn msg_cn(&self) -> String {
+        match self {
+            DisputeError::EmptyText => "请填写申诉内容".to_string(),
+            DisputeError::NotInvolved => "不是对局双方部落成员".to_string(),
+            DisputeError::AlreadyOpen => "该对局已有未裁定的申诉".to_string(),
+            DisputeError::SameSide => "只能由对方部落回应".to_string(),
+            DisputeError::Resolved => "申诉已裁定".to_string(),
+            DisputeError::InvalidPenalty => "处罚须指定本局部落和处罚类型".to_string(),
+            DisputeError::ReverseFailed => "仅内部匹配可逆转".to_string(),
+            DisputeError::AlreadyReversed => "该对局已逆转".to_string(),
+            DisputeError::Database(e) => format!("数据库错误: {e}"),
+        }
+    }
+}
+
+/// # 用户在对局中所属的部落
+async fn user_side(
+    pool: &Pool<Postgres>,
+    user_info: &UserInfo,
+    track: &Track,
+) -> Result<Vec<Uuid>, Error> {
+    Ok(user_info
+        .user_clans(pool)
+        .await?
+        .iter()
+        .map(|c| c.get_id())
+        .filter(|id| *id == track.self_clan_id || *id == track.rival_clan_id)
+        .collect())
+}
+
+impl TrackDispute {
+    /// # 申诉列表
+    /// 未裁定的在前
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.track_dispute order by resolve_time desc nulls first, create_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from orange.track_dispute where id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 对局的申诉
+    pub async fn select_track(pool: &Pool<Postgres>, track_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.track_dispute where track_id = $1 order by create_time desc")
+            .bind(track_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 发起申诉
+    pub async fn open(
+        pool: &Pool<Postgres>,
+        user_info: &UserInfo,
+        dispute_new: &DisputeNew,
+    ) -> Result<PgQueryResult, DisputeError> {
+        if dispute_new.text.trim().is_empty() {
+            return Err(DisputeError::EmptyText);
+        }
+        let track = Track::select(pool, dispute_new.track_id).await?;
+        let Some(clan_id) = user_side(pool, user_info, &track).await?.first().copied() else {
+            return Err(DisputeError::NotInvolved);
+        };
+        let open: i64 = query_scalar(
+            "select count(id) from orange.track_dispute where track_id = $1 and status <> $2",
+        )
+        .bind(track.id)
+        .bind(DisputeStatus::Resolved)
+        .fetch_one(pool)
+        .await?;
+        if open > 0 {
+            return Err(DisputeError::AlreadyOpen);
+        }
+
+        log_info!("发起申诉 {} {}", track.id, dispute_new.text);
+        Ok(query("insert into orange.track_dispute values(DEFAULT, $1, $2, $3, $4, $5, NULL, NULL, NULL, NULL, $6, NULL, NULL, NULL, NULL, $7)")
+            .bind(track.id)
+            .bind(clan_id)
+            .bind(user_info.get_id())
+            .bind(dispute_new.text.trim())
+            .bind(Json(dispute_new.attachments.clone().unwrap_or_default()))
+            .bind(DisputeStatus::Open)
+            .bind(Utc::now())
+            .execute(pool)
+            .await?)
+    }
+
+    /// # 对方回应
+    /// 裁定前可多次回应，以最后一次为准
+    pub async fn respond(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        user_info: &UserInfo,
+        dispute_respond: &DisputeRespond,
+    ) -> Result<PgQueryResult, DisputeError> {
+        if dispute_respond.text.trim().is_empty() {
+            return Err(DisputeError::EmptyText);
+        }
+        let dispute = Self::select(pool, id).await?;
+        if dispute.status == DisputeStatus::Resolved {
+            return Err(DisputeError::Resolved);
+        }
+        let track = Track::select(pool, dispute.track_id).await?;
+        let sides = user_side(pool, user_info, &track).await?;
+        if sides.is_empty() {
+            return Err(DisputeError::NotInvolved);
+        }
+        if !sides.iter().any(|c| *c != dispute.clan_id) {
+            return Err(DisputeError::SameSide);
+        }
+
+        Ok(query("update orange.track_dispute set response = $1, response_attachments = $2, respond_by = $3, respond_time = $4, status = $5 where id = $6")
+            .bind(dispute_respond.text.trim())
+            .bind(Json(dispute_respond.attachments.clone().unwrap_or_default()))
+            .bind(user_info.get_id())
+            .bind(Utc::now())
+            .bind(DisputeStatus::Responded)
+            .bind(id)
+            .execute(pool)
+            .await?)
+    }
+
+    /// # 管理员裁定
+    /// 逆转走原有逆转流程，处罚写入奖惩记录
+    /// 裁定和逆转或处罚同一事务
+    pub async fn resolve(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        resolve_by: Uuid,
+        dispute_resolve: &DisputeResolve,
+    ) -> Result<PgQueryResult, DisputeError> {
+        let dispute = Self::select(pool, id).await?;
+        if dispute.status == DisputeStatus::Resolved {
+            return Err(DisputeError::Resolved);
+        }
+        let track = Track::select(pool, dispute.track_id).await?;
+
+        let mut tx = pool.begin().await?;
+        let res = query("update orange.track_dispute set status = $1, resolution = $2, resolve_note = $3, resolve_by = $4, resolve_time = $5 where id = $6 and status <> $1")
+            .bind(DisputeStatus::Resolved)
+            .bind(dispute_resolve.resolution)
+            .bind(&dispute_resolve.note)
+            .bind(resolve_by)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        // 并发裁定时只有一次生效
+        if res.rows_affected() == 0 {
+            return Err(DisputeError::Resolved);
+        }
+
+        match dispute_resolve.resolution {
+            DisputeResolution::Upheld => {}
+            DisputeResolution::Reversed => {
+                registration::reverse_in(&mut tx, track.id).await?;
+            }
+            DisputeResolution::Penalty => {
+                let reward_type = dispute_resolve
+                    .reward_type
+                    .clone()
+                    .unwrap_or(RewardType::Penalty);
+                let penalty = dispute_resolve
+                    .penalty_clan_id
+                    .filter(|c| *c == track.self_clan_id || *c == track.rival_clan_id)
+                    .map(|clan_id| {
+                        OperateLog::new(
+                            track.round_id,
+                            clan_id,
+                            reward_type,
+                            dispute_resolve.note.clone(),
+                        )
+                    })
+                    .filter(|o| o.is_reward_penalty())
+                    .ok_or(DisputeError::InvalidPenalty)?;
+                penalty.new_reward_in(&mut tx).await?;
+            }
+        }
+        tx.commit().await?;
+
+        log_info!("裁定申诉 {} {:?}", id, dispute_resolve.resolution);
+        Ok(res)
+    }
+}
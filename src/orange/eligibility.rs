@@ -0,0 +1,339 @@
+use crate::{
+    api::{self, MiddleReadCompo},
+    orange::{Clan, ClanStatus},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{
+    Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as, types::Json,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 准入规则
+/// 每种规则一条，管理员可启停和调整阈值
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct EligibilityRule {
+    pub rule_type: RuleType,
+    /// # 阈值（本均区间规则为允许偏差）
+    pub value: Option<f32>,
+    pub enabled: bool,
+    #[serde(skip_deserializing)]
+    pub update_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum RuleType {
+    /// # 公开对战日志
+    #[default]
+    WarLogPublic = 1,
+    /// # 最少成员数
+    MinMembers = 2,
+    /// # 本均在中间库区间内
+    ThAvgBand = 3,
+    /// # 最低部落等级
+    MinClanLevel = 4,
+}
+
+/// # 未通过的规则
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RuleFailure {
+    pub rule_type: RuleType,
+    pub msg_en: String,
+    pub msg_cn: String,
+}
+
+/// # 准入锁定记录
+/// 只有此表内未解除的锁定会被自动解锁，手动锁定不受影响
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ClanLock {
+    pub id: Uuid,
+    pub clan_id: Uuid,
+    pub reasons: Json<Vec<RuleFailure>>,
+    pub lock_time: DateTime<Utc>,
+    pub check_time: DateTime<Utc>,
+    pub unlock_time: Option<DateTime<Utc>>,
+}
+
+/// # 检查结果
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct EligibilityReport {
+    pub checked: usize,
+    pub locked: Vec<Uuid>,
+    pub unlocked: Vec<Uuid>,
+}
+
+impl Display for ClanLock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Clan Lock {}\n - Clan: {}\n - Reasons: {:?}\n - Lock: {} | Unlock: {:?}",
+            self.id, self.clan_id, self.reasons.0, self.lock_time, self.unlock_time
+        )
+    }
+}
+
+impl RuleFailure {
+    fn new(rule_type: RuleType, msg_en: String, msg_cn: String) -> Self {
+        Self {
+            rule_type,
+            msg_en,
+            msg_cn,
+        }
+    }
+}
+
+impl EligibilityRule {
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.eligibility_rule order by rule_type")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn upsert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.eligibility_rule values($1, $2, $3, $4) on conflict (rule_type) do update set value = $2, enabled = $3, update_time = $4")
+            .bind(self.rule_type)
+            .bind(self.value)
+            .bind(self.enabled)
+            .bind(Utc::now())
+            .execute(pool)
+            .await
+    }
+
+    /// # 单条规则校验
+    /// 本均区间只对同服务器的中间库数据生效
+    fn check(&self, api_clan: &api::Clan, compo: Option<&MiddleReadCompo>) -> Option<RuleFailure> {
+        let value = self.value.unwrap_or_default();
+        match self.rule_type {
+            RuleType::WarLogPublic => {
+                (!api_clan.is_war_log_public.unwrap_or_default()).then(|| {
+                    RuleFailure::new(
+                        self.rule_type,
+                        "War log is not public".to_string(),
+                        "对战日志未公开".to_string(),
+                    )
+                })
+            }
+            RuleType::MinMembers => {
+                let members = api_clan.members.unwrap_or_default();
+                ((members as f32) < value).then(|| {
+                    RuleFailure::new(
+                        self.rule_type,
+                        format!("Members {members} below {value}"),
+                        format!("成员数 {members} 低于 {value}"),
+                    )
+                })
+            }
+            RuleType::MinClanLevel => {
+                let level = api_clan.clan_level.unwrap_or_default();
+                ((level as f32) < value).then(|| {
+                    RuleFailure::new(
+                        self.rule_type,
+                        format!("Clan level {level} below {value}"),
+                        format!("部落等级 {level} 低于 {value}"),
+                    )
+                })
+            }
+            RuleType::ThAvgBand => {
+                let compo = compo?;
+                let th_avg = api_clan.th_avg();
+                let min = compo.get_min_th_avg() - value;
+                let max = compo.get_max_th_avg() + value;
+                (th_avg < min || th_avg > max).then(|| {
+                    RuleFailure::new(
+                        self.rule_type,
+                        format!("TH average {th_avg:.2} outside {min:.2}-{max:.2}"),
+                        format!("本均 {th_avg:.2} 不在 {min:.2}-{max:.2} 区间"),
+                    )
+                })
+            }
+        }
+    }
+}
+
+/// # 按规则校验部落
+pub fn evaluate(
+    rules: &[EligibilityRule],
+    api_clan: &api::Clan,
+    compo: Option<&MiddleReadCompo>,
+) -> Vec<RuleFailure> {
+    rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .filter_map(|rule| rule.check(api_clan, compo))
+        .collect()
+}
+
+impl ClanLock {
+    /// # 部落当前锁定
+    pub async fn select_clan(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Self, Error> {
+        query_as("select * from orange.clan_lock where clan_id = $1 and unlock_time is null")
+            .bind(clan_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 部落锁定历史
+    pub async fn select_history(pool: &Pool<Postgres>, clan_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.clan_lock where clan_id = $1 order by lock_time desc")
+            .bind(clan_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn insert(
+        pool: &Pool<Postgres>,
+        clan_id: Uuid,
+        reasons: Vec<RuleFailure>,
+    ) -> Result<PgQueryResult, Error> {
+        let now = Utc::now();
+        query("insert into orange.clan_lock values(DEFAULT, $1, $2, $3, $3, NULL)")
+            .bind(clan_id)
+            .bind(Json(reasons))
+            .bind(now)
+            .execute(pool)
+            .await
+    }
+
+    async fn update_reasons(
+        &self,
+        pool: &Pool<Postgres>,
+        reasons: Vec<RuleFailure>,
+    ) -> Result<PgQueryResult, Error> {
+        query("update orange.clan_lock set reasons = $1, check_time = $2 where id = $3")
+            .bind(Json(reasons))
+            .bind(Utc::now())
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    async fn unlock(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let now = Utc::now();
+        query("update orange.clan_lock set check_time = $1, unlock_time = $1 where id = $2")
+            .bind(now)
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 准入检查
+    /// 正常部落不合规则锁定，准入锁定的部落合规后恢复正常
+    pub async fn check_all(pool: &Pool<Postgres>) -> Result<EligibilityReport, Error> {
+        let rules = EligibilityRule::select_all(pool).await?;
+        let compo = match MiddleReadCompo::try_get().await {
+            Ok(compo) => Some(compo),
+            Err(e) => {
+                log_warn!("ReadCompo {e}");
+                None
+            }
+        };
+        let mut report = EligibilityReport::default();
+
+        for clan in Clan::select_sync(pool).await? {
+            let lock = Self::select_clan(pool, clan.get_id()).await.ok();
+            let is_ready = clan.status.is_some_and(|s| s == ClanStatus::Ready);
+            if !is_ready && lock.is_none() {
+                continue;
+            }
+
+            let Some(tag) = clan.tag.as_ref() else {
+                log_warn!("部落无标签 {}", clan.get_id());
+                continue;
+            };
+            let api_clan = api::Clan::get(tag).await;
+            tokio::time::sleep(api::API_INTERVAL).await;
+            if api_clan.tag.is_none() {
+                log_warn!("准入检查跳过 {:?} {:?}", clan.tag, api_clan.reason);
+                continue;
+            }
+            let server_compo = compo
+                .as_ref()
+                .filter(|c| c.is_global() == clan.is_global.unwrap_or(true));
+            let failures = evaluate(&rules, &api_clan, server_compo);
+            report.checked += 1;
+
+            let mut status_clan = Clan {
+                id: clan.id,
+                ..Default::default()
+            };
+            match (lock, failures.is_empty()) {
+                (None, false) => {
+                    log_info!("准入锁定 {:?} {:?}", clan.tag, failures);
+                    status_clan.status = Some(ClanStatus::Locked);
+                    status_clan.update_status(pool).await?;
+                    Self::insert(pool, clan.get_id(), failures).await?;
+                    report.locked.push(clan.get_id());
+                }
+                (Some(lock), true) => {
+                    log_info!("准入解锁 {:?}", clan.tag);
+                    // 锁定期间被管理员改为其他状态时不覆盖
+                    if clan.status.is_some_and(|s| s == ClanStatus::Locked) {
+                        status_clan.status = Some(ClanStatus::Ready);
+                        status_clan.update_status(pool).await?;
+                        report.unlocked.push(clan.get_id());
+                    }
+                    lock.unlock(pool).await?;
+                }
+                (Some(lock), false) => {
+                    // 锁定期间被改回正常时重新锁定
+                    if clan.status.is_some_and(|s| s == ClanStatus::Ready) {
+                        log_info!("准入重新锁定 {:?} {:?}", clan.tag, failures);
+                        status_clan.status = Some(ClanStatus::Locked);
+                        status_clan.update_status(pool).await?;
+                        report.locked.push(clan.get_id());
+                    }
+                    lock.update_reasons(pool, failures).await?;
+                }
+                (None, true) => {}
+            }
+        }
+        log_info!(
+            "准入检查完成 {} 锁定 {} 解锁 {}",
+            report.checked,
+            report.locked.len(),
+            report.unlocked.len()
+        );
+        Ok(report)
+    }
+}
+
+#[test]
+fn test_evaluate() {
+    let rules = vec![
+        EligibilityRule {
+            rule_type: RuleType::WarLogPublic,
+            enabled: true,
+            ..Default::default()
+        },
+        EligibilityRule {
+            rule_type: RuleType::MinMembers,
+            value: Some(30.0),
+            enabled: true,
+            ..Default::default()
+        },
+        EligibilityRule {
+            rule_type: RuleType::MinClanLevel,
+            value: Some(10.0),
+            enabled: false,
+            ..Default::default()
+        },
+    ];
+    let mut api_clan = api::Clan {
+        is_war_log_public: Some(true),
+        members: Some(40),
+        clan_level: Some(5),
+        ..Default::default()
+    };
+    assert!(evaluate(&rules, &api_clan, None).is_empty());
+
+    api_clan.is_war_log_public = Some(false);
+    api_clan.members = Some(20);
+    let failures = evaluate(&rules, &api_clan, None);
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0].rule_type, RuleType::WarLogPublic);
+    assert_eq!(failures[1].rule_type, RuleType::MinMembers);
+}
@@ -1,34 +1,68 @@
+mod blacklist;
 mod clan;
+mod clan_apply;
+mod clan_compo;
+mod clan_history;
 mod clan_point;
+mod clan_profile;
+mod clan_roster;
+mod clan_verify;
+mod dispute;
+mod eligibility;
 mod operate_log;
+mod player_blacklist;
 mod round;
+mod round_report;
 mod series;
 mod track;
+mod track_correct;
+mod war_stat;
 
 use crate::{
     AppState,
     api::{self, War},
-    core::registration,
+    core::{
+        registration::{self, ReverseError},
+        tag::{ClanTag, PlayerTag},
+    },
     orange::operate_log::{OperateLog, RewardType},
-    system::{User, UserInfo},
+    safety::{ApiKey, ApiScope},
+    system::{AuditLog, User, UserInfo},
     util::RestApi,
 };
 use axum::{
     Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use axum_auth::AuthBearer;
 
+use blacklist::AppealReview;
+pub use blacklist::Blacklist;
 pub use clan::*;
+use clan_apply::{ApplyResponse, ApplyReview, ClanApply};
+use clan_compo::ClanCompo;
+use clan_history::ClanHistory;
 pub use clan_point::*;
-pub use round::Round;
+pub use clan_profile::ClanProfile;
+use clan_roster::ClanRoster;
+use clan_verify::{ClanVerify, ClanVerifyNew, VerifyResponse};
+use dispute::{DisputeError, DisputeNew, DisputeResolve, DisputeRespond, TrackDispute};
+pub use eligibility::ClanLock;
+use eligibility::EligibilityRule;
+use player_blacklist::PlayerAlert;
+pub use player_blacklist::PlayerBlacklist;
+pub use round::{Round, RoundError, RoundNew, RoundServer, RoundTransition};
+use round_report::RoundReport;
 use serde_json::Value;
 pub use track::*;
+use track_correct::{CorrectError, TrackCorrect, TrackCorrectNew};
 use uuid::Uuid;
 use void_log::{log_error, log_info, log_warn};
+use war_stat::WarAttack;
+pub use war_stat::WarStat;
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -39,6 +73,16 @@ pub fn router() -> Router<AppState> {
         .route("/clan/{id}", get(clan).delete(clan_delete))
         .route("/clan/{tag}/{is_global}", get(clan_tag))
         .route("/clan_info/{tag}", get(clan_info))
+        .route("/clan_profile/{id}", get(clan_profiles))
+        .route("/clan_history/{id}", get(clan_histories))
+        .route("/clan_roster/{id}", get(clan_rosters))
+        .route("/clan_compo", get(clan_compos))
+        .route("/clan_compo/{id}", get(clan_compo))
+        .route("/roster_report/{id}", get(roster_report))
+        // 部落归档
+        .route("/clan_archived", get(clans_archived))
+        .route("/clan_restore/{id}", put(clan_restore))
+        .route("/clan_purge/{id}", delete(clan_purge))
         // 部落积分相关
         .route("/clan_point", get(clan_point_all).put(clan_reward_point))
         .route("/clan_point/{id}", get(clan_point))
@@ -46,6 +90,9 @@ pub fn router() -> Router<AppState> {
         .route("/round", get(rounds).post(round_insert))
         .route("/round_{page}/{page_size}", get(rounds_page))
         .route("/last_round", get(last_round))
+        .route("/round/{id}", put(round_update).delete(round_cancel))
+        .route("/round_state/{id}", put(round_state))
+        .route("/round_report/{id}", get(round_report))
         // 对战记录相关
         .route("/track", get(tracks).post(new_track))
         .route("/track_{page}/{page_size}", get(tracks_page))
@@ -53,10 +100,51 @@ pub fn router() -> Router<AppState> {
             "/track/{id}",
             get(track_round).post(reverse_track).delete(delete_track),
         )
+        .route(
+            "/track_correct/{id}",
+            get(track_corrects).post(track_correct),
+        )
+        // 对局申诉
+        .route("/track_dispute", get(track_disputes).post(dispute_open))
+        .route("/track_dispute/{id}", get(track_dispute))
+        .route("/dispute_respond/{id}", put(dispute_respond))
+        .route("/dispute_resolve/{id}", put(dispute_resolve))
+        // 对战统计
+        .route("/war_stat/{id}", get(war_stat))
+        .route("/war_compliance/{id}", get(war_compliance))
+        .route("/player_attack/{tag}", get(player_attacks))
         // 用户关联相关
         .route("/user_clans", get(user_clans))
         .route("/user_clans/{id}", get(userid_clans))
         .route("/clan_user", post(insert_cu).delete(delete_cu))
+        // 加盟申请
+        .route("/clan_apply", get(clan_applies).post(clan_apply_insert))
+        .route("/clan_apply/{id}", get(clan_apply).put(clan_apply_review))
+        // 部落归属验证
+        .route("/clan_verify", get(clan_verifies).post(clan_verify_insert))
+        .route("/clan_verify/{id}", post(clan_verify_check))
+        // 准入规则
+        .route(
+            "/eligibility_rule",
+            get(eligibility_rules).put(eligibility_rule_update),
+        )
+        .route("/eligibility", post(eligibility_check))
+        .route("/clan_lock/{id}", get(clan_locks))
+        // 黑名单
+        .route("/blacklist", get(blacklists).post(blacklist_insert))
+        .route("/blacklist/{id}", get(blacklist).delete(blacklist_release))
+        .route(
+            "/blacklist_appeal/{id}",
+            post(blacklist_appeal).put(blacklist_appeal_review),
+        )
+        // 玩家黑名单
+        .route(
+            "/player_blacklist",
+            get(player_blacklists).post(player_blacklist_insert),
+        )
+        .route("/player_blacklist/{id}", delete(player_blacklist_release))
+        .route("/player_alert", get(player_alerts))
+        .route("/player_alert/{id}", put(player_alert_handle))
         // 操作日志相关
         .route("/operate_log", get(operate_logs))
         .route("/operate_log_{page}/{page_size}", get(operate_logs_page))
@@ -68,7 +156,7 @@ async fn clans(
     AuthBearer(token): AuthBearer,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*clan*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::ClanSelect).await {
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
@@ -83,7 +171,7 @@ async fn clans_page(
     Path((page, page_size)): Path<(i64, i64)>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*clan*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::ClanSelect).await {
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
@@ -121,15 +209,14 @@ async fn clan(
 async fn clan_tag(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    Path((tag, is_global)): Path<(String, bool)>,
+    Path((tag, is_global)): Path<(ClanTag, bool)>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*clan*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::ClanSelect).await {
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
     log_info!("Clan {} {}", &tag, is_global);
-    let tag = format!("#{tag}").to_uppercase();
 
     if let Ok(clan) = Clan::select_tag(&app_state.pool, &tag, is_global).await {
         if clan.status.is_some_and(|x| x == ClanStatus::Ready) {
@@ -142,48 +229,198 @@ async fn clan_tag(
     }
 }
 
-async fn clan_info(AuthBearer(token): AuthBearer, Path(tag): Path<String>) -> impl IntoResponse {
+async fn clan_info(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(tag): Path<ClanTag>,
+) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*clan*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::ClanSelect).await {
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
-    let tag = format!("#{tag}").to_uppercase();
     let res = api::Clan::get(&tag).await.info();
     (StatusCode::OK, Json(res))
 }
 
-async fn clan_search(
+/// # 部落历史快照
+async fn clan_profiles(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    Json(text): Json<String>,
+    Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*clan*select") {
-        return (StatusCode::UNAUTHORIZED, Json::default());
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    log_info!("Clan {}", &text);
-    if let Ok(clan) = Clan::select_search(&app_state.pool, &text).await {
-        (StatusCode::OK, Json(clan))
+    let res = ClanProfile::select_clan(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
     } else {
-        (StatusCode::GONE, Json::default())
+        (StatusCode::GONE, RestApi::error())
     }
 }
 
-async fn clan_insert(
+/// # 部落标签/名称历史
+async fn clan_histories(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    headers: HeaderMap,
-    Json(data): Json<Clan>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ClanHistory::select_clan(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 部落本轮成员
+async fn clan_rosters(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let is_global = match Clan::select(&app_state.pool, id).await {
+        Ok(clan) => clan.is_global.unwrap_or(true),
+        Err(_) => return (StatusCode::GONE, RestApi::error()),
+    };
+    let round = Round::select_last(&app_state.pool, is_global)
+        .await
+        .unwrap_or_default();
+    let res = ClanRoster::select_clan(&app_state.pool, round.get_id(), id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 本轮阵容对比
+/// 各部落相对联盟本均区间的位置和趋势，按服务器查询
+async fn clan_compos(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Query(server): Query<RoundServer>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let round = Round::select_last(&app_state.pool, server.is_global())
+        .await
+        .unwrap_or_default();
+    let res = ClanCompo::placement(&app_state.pool, round.get_id()).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 部落历轮阵容
+async fn clan_compo(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ClanCompo::select_clan(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 轮次成员报告
+/// 成员变动和跳部落可疑成员
+async fn roster_report(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     if !UserInfo::get_user(&token)
         .await
-        .unwrap()
+        .unwrap_or_default()
         .check_role("admin")
     {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match Round::select(&app_state.pool, id).await {
+        Ok(round) => ClanRoster::report(&app_state.pool, &round).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn clan_search(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Json(search): Json<ClanSearch>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::ClanSelect).await {
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    log_info!("Clan Search {:?}", &search);
+    let res = Clan::select_search(&app_state.pool, &search).await;
+    let count = Clan::count_search(&app_state.pool, &search).await;
+    if let Ok(r) = res {
+        (
+            StatusCode::OK,
+            RestApi::new_successful(r).data_count(count).builder(),
+        )
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn clan_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    headers: HeaderMap,
+    Json(data): Json<Clan>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
         log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
@@ -197,6 +434,10 @@ async fn clan_insert(
     };
 
     if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "clan", &headers)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
@@ -210,13 +451,24 @@ async fn clan_update(
     Json(data): Json<Clan>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, Json::default());
-    }
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, Json::default());
+        }
+    };
     // ********************鉴权********************
 
     log_info!("{:?}", data);
+    let before = Clan::select(&app_state.pool, data.get_id()).await.ok();
+    let action = if data.status.is_some() {
+        "update_status"
+    } else if headers.get("Auto").is_some() {
+        "auto_update"
+    } else {
+        "update"
+    };
     let res = if data.status.is_some() {
         log_info!("update status");
         data.update_status(&app_state.pool).await
@@ -230,6 +482,12 @@ async fn clan_update(
     };
 
     if let Ok(r) = res {
+        AuditLog::new(&user_info, action, "clan", &headers)
+            .entity_id(data.get_id())
+            .before(&before)
+            .after(&Clan::select(&app_state.pool, data.get_id()).await.ok())
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
@@ -239,24 +497,128 @@ async fn clan_update(
 async fn clan_delete(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, Json::default());
+    }
+    // ********************鉴权********************
+
+    let before = Clan::select(&app_state.pool, id).await.ok();
+    let res = Clan::delete(&app_state.pool, id, user_info.get_id()).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "clan", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .after(&Clan::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, Json(r.rows_affected()))
+    } else {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
+    }
+}
+
+/// # 已归档部落
+async fn clans_archived(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     if !UserInfo::get_user(&token)
         .await
-        .unwrap()
+        .unwrap_or_default()
         .check_role("admin")
     {
         log_warn!("UNAUTHORIZED");
-        return (StatusCode::UNAUTHORIZED, Json::default());
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    let res = Clan::delete(&app_state.pool, id).await;
+    let res = Clan::select_archived(&app_state.pool).await;
     if let Ok(r) = res {
-        (StatusCode::OK, Json(r.rows_affected()))
+        (StatusCode::OK, RestApi::successful(r))
     } else {
-        (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 恢复部落
+async fn clan_restore(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Clan::select(&app_state.pool, id).await.ok();
+    match Clan::restore(&app_state.pool, id).await {
+        Ok(r) if r.rows_affected() > 0 => {
+            AuditLog::new(&user_info, "restore", "clan", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&Clan::select(&app_state.pool, id).await.ok())
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        _ => (
+            StatusCode::GONE,
+            RestApi::failed("Clan not archived", "部落不存在或未归档"),
+        ),
+    }
+}
+
+/// # 彻底删除部落
+/// 仍有对局等历史引用时拒绝
+async fn clan_purge(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let references = Clan::references(&app_state.pool, id).await;
+    if references > 0 {
+        return (
+            StatusCode::CONFLICT,
+            RestApi::failed(
+                format!("Clan still referenced by {references} records"),
+                format!("部落仍有 {references} 条历史记录引用"),
+            ),
+        );
+    }
+    let before = Clan::select(&app_state.pool, id).await.ok();
+    if let Ok(r) = Clan::purge(&app_state.pool, id).await {
+        AuditLog::new(&user_info, "purge", "clan", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::GONE,
+            RestApi::failed("Clan not archived", "部落不存在或未归档"),
+        )
     }
 }
 
@@ -297,9 +659,12 @@ async fn rounds_page(
     )
 }
 
+/// # 最新轮次
+/// 未指定服务器时取用户第一个部落所在服务器
 async fn last_round(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    Query(server): Query<RoundServer>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     log_info!("User Token {}", token);
@@ -320,7 +685,10 @@ async fn last_round(
         );
     }
 
-    let res = Round::select_last(&app_state.pool).await;
+    let is_global = server
+        .is_global
+        .unwrap_or_else(|| ucs[0].is_global.unwrap_or(true));
+    let res = Round::select_last(&app_state.pool, is_global).await;
     if let Ok(r) = res {
         (StatusCode::OK, RestApi::successful(r))
     } else {
@@ -331,66 +699,224 @@ async fn last_round(
 async fn round_insert(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    Json(data): Json<Value>,
+    header_map: HeaderMap,
+    Json(data): Json<RoundNew>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     log_info!("User Token {}", token);
-    if !UserInfo::get_user(&token)
-        .await
-        .unwrap()
-        .check_role("admin")
-    {
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
         log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    if let Some(time_str) = data["time"].as_str() {
-        let res = Round::insert(time_str, &app_state.pool).await;
-        let rows_affected = res.unwrap_or_default().rows_affected();
-        (
-            StatusCode::OK,
-            RestApi::new("Succeeded", "发布成功", Some(rows_affected as i64)).builder(),
-        )
-    } else {
-        (
-            StatusCode::UNPROCESSABLE_ENTITY,
-            RestApi::new("Error: Time String", "失败: 格式错误", Some(-2)).builder(),
-        )
+    match Round::insert(&data, &app_state.pool).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "insert", "round", &header_map)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (
+                StatusCode::OK,
+                RestApi::new("Succeeded", "发布成功", Some(r.rows_affected() as i64)).builder(),
+            )
+        }
+        Err(e) => {
+            log_warn!("Round {e}");
+            let status = match e {
+                RoundError::Duplicate => StatusCode::CONFLICT,
+                RoundError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
     }
 }
 
-async fn tracks(
+/// # 轮次改期
+/// 仅未开放登记的轮次可改
+async fn round_update(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<RoundNew>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, Json::default());
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    let res = Track::select_all(&app_state.pool).await.unwrap();
-    (StatusCode::OK, Json(res))
+    let before = Round::select(&app_state.pool, id).await.ok();
+    match Round::reschedule(&app_state.pool, id, &data).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "update", "round", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&Round::select(&app_state.pool, id).await.ok())
+                .record(&app_state.pool)
+                .await;
+            (
+                StatusCode::OK,
+                RestApi::successful(r.rows_affected() as i64),
+            )
+        }
+        Err(e) => {
+            log_warn!("Round {e}");
+            let status = match e {
+                RoundError::Duplicate | RoundError::InvalidState(_) => StatusCode::CONFLICT,
+                RoundError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+                RoundError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
+    }
 }
 
-async fn tracks_page(
+/// # 取消轮次
+/// 撤销本轮全部对局并返回撤销明细
+async fn round_cancel(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
-    Path((page, page_size)): Path<(i64, i64)>,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    let res = Track::select_page(&app_state.pool, page, page_size)
-        .await
-        .unwrap();
-
+    let before = Round::select(&app_state.pool, id).await.ok();
+    match Round::cancel(&app_state.pool, id).await {
+        Ok(report) => {
+            AuditLog::new(&user_info, "cancel", "round", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&report)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(report))
+        }
+        Err(e) => {
+            log_warn!("Round {e}");
+            let status = match e {
+                RoundError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+                RoundError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::CONFLICT,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
+    }
+}
+
+/// # 轮次报告
+/// 登记、积分和奖惩统计，附上一轮对比
+async fn round_report(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match Round::select(&app_state.pool, id).await {
+        Ok(round) => RoundReport::new(&app_state.pool, &round).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 变更轮次状态
+/// 只能逐级推进，结算需管理员操作
+async fn round_state(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<RoundTransition>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Round::select(&app_state.pool, id).await.ok();
+    match Round::transition(&app_state.pool, id, data.state).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "state", "round", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(e) => {
+            log_warn!("Round {e}");
+            let status = match e {
+                RoundError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+                RoundError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::CONFLICT,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
+    }
+}
+
+async fn tracks(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, Json::default());
+    }
+    // ********************鉴权********************
+
+    let res = Track::select_all(&app_state.pool).await.unwrap();
+    (StatusCode::OK, Json(res))
+}
+
+async fn tracks_page(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path((page, page_size)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Track::select_page(&app_state.pool, page, page_size)
+        .await
+        .unwrap();
+
     let count = Track::count(&app_state.pool).await;
     (
         StatusCode::OK,
@@ -437,26 +963,19 @@ async fn track_round(
 async fn new_track(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<Value>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
     log_info!("User Token {}", token);
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
-    }
-    // ********************鉴权********************
-
-    // 检查登记时间
-    let round = Round::select_last(&app_state.pool)
-        .await
-        .unwrap_or_default();
-    if round.check_not_now().await {
-        return (
-            StatusCode::UNPROCESSABLE_ENTITY,
-            RestApi::failed("Round not in War", "本轮未开战"),
-        );
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
     };
+    // ********************鉴权********************
 
     // 默认国际服
     let is_global = if let Some(global) = data.get("is_global") {
@@ -465,29 +984,58 @@ async fn new_track(
         true
     };
 
-    // 获取先后手
-    let last = if let Some(l) = data.get("last") {
-        l.as_bool().unwrap_or_default()
-    } else {
-        false
+    // 检查登记时间
+    let round = Round::select_open(&app_state.pool, is_global)
+        .await
+        .unwrap_or_default();
+    if !round.get_state().can_register() {
+        let e = RoundError::InvalidState(round.get_state());
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed(e.msg_en(), e.msg_cn()),
+        );
     };
+
     log_info!(
-        "[检查信息]\n1.检查登记时间 {}\n2.是否国际服 {is_global}\n3.是否后手 {last}",
+        "[检查信息]\n1.检查登记时间 {}\n2.是否国际服 {is_global}",
         round.get_code()
     );
 
     // 获取本家标签
-    let self_tag = data["self_tag"].as_str().unwrap_or_default().to_string();
+    let self_tag = match ClanTag::new(data["self_tag"].as_str().unwrap_or_default()) {
+        Ok(tag) => tag,
+        Err(e) => {
+            log_warn!("本家标签错误 {e}");
+            return (
+                StatusCode::BAD_REQUEST,
+                RestApi::failed("Invalid self tag", "本家标签错误"),
+            );
+        }
+    };
 
     // 获取对家标签
     let rival_tag = if let Some(tag) = data.get("rival_tag") {
         log_info!("启用手动登记");
-        tag.as_str().unwrap_or_default().to_string()
+        match ClanTag::new(tag.as_str().unwrap_or_default()) {
+            Ok(tag) => tag,
+            Err(e) => {
+                log_warn!("对家标签错误 {e}");
+                return (
+                    StatusCode::BAD_REQUEST,
+                    RestApi::failed("Invalid rival tag", "对家标签错误"),
+                );
+            }
+        }
     } else if is_global {
         log_info!("国际服自动登记");
         // 查对面标签
         let war = War::get(&self_tag).await;
-        if let Some(opponent_clan_tag) = war.opponent.unwrap_or_default().tag {
+        if let Some(opponent_clan_tag) = war
+            .opponent
+            .unwrap_or_default()
+            .tag
+            .and_then(|tag| ClanTag::new(&tag).ok())
+        {
             opponent_clan_tag
         } else {
             // 未开战
@@ -500,14 +1048,23 @@ async fn new_track(
     } else {
         log_info!("国服无接口");
         return (
-            StatusCode::GONE,
-            RestApi::failed("China not Api", "国服无接口"),
+            StatusCode::BAD_REQUEST,
+            RestApi::failed(
+                "China server requires rival tag",
+                "国服无接口，请填写对家标签",
+            ),
         );
     };
     log_info!("登记0: 本家标签 {} | 对家标签 {}", &self_tag, &rival_tag);
 
+    // 先后手按标签数值判定，双方登记同一场结果一致
+    let last = !self_tag.is_first_mover(&rival_tag);
+    log_info!("登记0: 是否后手 {last}");
+
     // 上场轮次（用于查询两把重复）
-    let round2 = Round::select_last2(&app_state.pool).await;
+    let round2 = Round::select_previous(&app_state.pool, &round)
+        .await
+        .unwrap_or_default();
 
     // 查询本家加盟状态
     let self_clan = if let Ok(clan) = Clan::select_tag(&app_state.pool, &self_tag, is_global).await
@@ -609,8 +1166,9 @@ async fn new_track(
             (None, false, false)
         };
 
-    // 本轮已登记直接返回
-    if let Ok(track) = Track::select_registered(&app_state.pool, &first_point, &round).await {
+    // 本轮已登记直接返回（先手非正常部落时按后手查）
+    let registered_point = first_point.clone().or_else(|| last_point.clone());
+    if let Ok(track) = Track::select_registered(&app_state.pool, &registered_point, &round).await {
         log_info!("已登记 {}", track);
         return (StatusCode::OK, RestApi::successful(track));
     };
@@ -636,9 +1194,23 @@ async fn new_track(
         );
     }
 
-    // 添加Track获取输赢（本盟/中间库）
-    let track = if let Some(t) = Track::new(
+    // 添加Track获取输赢（黑名单/本盟/中间库）
+    let is_blacklist = first_clan
+        .status
+        .is_some_and(|s| s == ClanStatus::Blacklist)
+        || last_clan.status.is_some_and(|s| s == ClanStatus::Blacklist);
+    let track = if is_blacklist {
+        if let Some(t) = Track::blacklist(&app_state.pool, &first_clan, &last_clan, &round).await {
+            t
+        } else {
+            return (
+                StatusCode::CONFLICT,
+                RestApi::failed("Both clans are blacklisted", "双方均为黑名单部落"),
+            );
+        }
+    } else if let Some(t) = Track::new(
         &app_state.pool,
+        &round,
         first_point,
         last_point,
         &self_clan,
@@ -651,8 +1223,8 @@ async fn new_track(
         return (
             StatusCode::GONE,
             RestApi::failed(
-                "Please try again later or after 10 minutes",
-                "请稍后再试，或10分钟后",
+                "Middle library API failed, please try again later",
+                "中间库接口异常，请稍后再试",
             ),
         );
     };
@@ -665,8 +1237,8 @@ async fn new_track(
         return (StatusCode::CONFLICT, RestApi::successful(track));
     };
 
-    // 更新self
-    let self_point = if first_clan.id.is_some() {
+    // 更新self（黑名单对局不计分）
+    let self_point = if first_clan.id.is_some() && !is_blacklist {
         ClanPoint::new(track.self_clan_id, track.self_now_point)
             .insert_or_update(&app_state.pool)
             .await
@@ -677,7 +1249,7 @@ async fn new_track(
     };
 
     // 更新rival
-    let rival_point = if last_clan.id.is_some() {
+    let rival_point = if last_clan.id.is_some() && !is_blacklist {
         ClanPoint::new(track.rival_clan_id, track.rival_now_point)
             .insert_or_update(&app_state.pool)
             .await
@@ -694,10 +1266,15 @@ async fn new_track(
     );
 
     log_info!("新登记 {}", track);
+    AuditLog::new(&user_info, "register", "track", &header_map)
+        .after(&track)
+        .record(&app_state.pool)
+        .await;
     (StatusCode::OK, RestApi::successful(track))
 }
 
-async fn reverse_track(
+/// # 对局更正历史
+async fn track_corrects(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
     Path(id): Path<Uuid>,
@@ -705,30 +1282,81 @@ async fn reverse_track(
     // ********************鉴权********************
     if let Err(e) = UserInfo::get_user(&token).await {
         log_warn!("UNAUTHORIZED {e}");
-        return (
-            StatusCode::UNAUTHORIZED,
-            RestApi::new_unauthorized().builder(),
-        );
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
 
-    let res = registration::reverse(&app_state.pool, id).await;
+    let res = TrackCorrect::select_track(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
 
+/// # 更正对局
+/// 修改结果或类型并重算积分，须填写原因
+async fn track_correct(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<TrackCorrectNew>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    match TrackCorrect::correct(&app_state.pool, id, &data, user_info.get_id()).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "correct", "track", &header_map)
+                .entity_id(id)
+                .before(&r.original)
+                .after(&r.corrected)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r))
+        }
+        Err(e) => {
+            log_warn!("Track Correct {e}");
+            let status = match e {
+                CorrectError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+                CorrectError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                CorrectError::EmptyReason => StatusCode::BAD_REQUEST,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
+    }
+}
+
+/// # 申诉列表
+async fn track_disputes(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = TrackDispute::select_all(&app_state.pool).await;
     if let Ok(r) = res {
-        (
-            StatusCode::OK,
-            RestApi::new_successful(r.rows_affected()).builder(),
-        )
+        (StatusCode::OK, RestApi::successful(r))
     } else {
-        (
-            StatusCode::UNPROCESSABLE_ENTITY,
-            RestApi::new_error().builder(),
-        )
+        (StatusCode::GONE, RestApi::error())
     }
 }
 
-/// # 解除本场登记
-async fn delete_track(
+/// # 对局的申诉
+async fn track_dispute(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
     Path(id): Path<Uuid>,
@@ -739,12 +1367,210 @@ async fn delete_track(
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
+
+    let res = TrackDispute::select_track(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+fn dispute_failed(e: DisputeError) -> (StatusCode, Json<RestApi<u64>>) {
+    log_warn!("Track Dispute {e}");
+    let status = match e {
+        DisputeError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+        DisputeError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        DisputeError::NotInvolved | DisputeError::SameSide => StatusCode::FORBIDDEN,
+        DisputeError::AlreadyOpen | DisputeError::Resolved | DisputeError::AlreadyReversed => {
+            StatusCode::CONFLICT
+        }
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+    (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+}
+
+/// # 发起申诉
+/// 对局任一方部落成员可发起
+async fn dispute_open(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<DisputeNew>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    match TrackDispute::open(&app_state.pool, &user_info, &data).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "open", "track_dispute", &header_map)
+                .entity_id(data.track_id)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(e) => dispute_failed(e),
+    }
+}
+
+/// # 回应申诉
+/// 仅对方部落成员可回应
+async fn dispute_respond(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<DisputeRespond>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    match TrackDispute::respond(&app_state.pool, id, &user_info, &data).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "respond", "track_dispute", &header_map)
+                .entity_id(id)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(e) => dispute_failed(e),
+    }
+}
+
+/// # 裁定申诉
+async fn dispute_resolve(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<DisputeResolve>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = TrackDispute::select(&app_state.pool, id).await.ok();
+    match TrackDispute::resolve(&app_state.pool, id, user_info.get_id(), &data).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "resolve", "track_dispute", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(e) => dispute_failed(e),
+    }
+}
+
+async fn reverse_track(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (
+                StatusCode::UNAUTHORIZED,
+                RestApi::new_unauthorized().builder(),
+            );
+        }
+    };
+    // ********************鉴权********************
+
+    let before = Track::select(&app_state.pool, id).await.ok();
+    let res = registration::reverse(&app_state.pool, id).await;
+
+    match res {
+        Ok(r) => {
+            AuditLog::new(&user_info, "reverse", "track", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .record(&app_state.pool)
+                .await;
+            (
+                StatusCode::OK,
+                RestApi::new_successful(r.rows_affected()).builder(),
+            )
+        }
+        Err(e) => {
+            log_warn!("Track Reverse {e}");
+            let status = match e {
+                ReverseError::Database(sqlx::Error::RowNotFound) => StatusCode::GONE,
+                ReverseError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                ReverseError::AlreadyReversed => StatusCode::CONFLICT,
+                ReverseError::NotInternal => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, RestApi::failed(e.msg_en(), e.msg_cn()))
+        }
+    }
+}
+
+/// # 解除本场登记
+async fn delete_track(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
     log_info!("解除登记：鉴权通过");
 
+    let before = Track::select(&app_state.pool, id).await.ok();
+    if let Some(track) = &before {
+        let round = Round::select(&app_state.pool, track.round_id)
+            .await
+            .unwrap_or_default();
+        if !round.get_state().can_adjust() {
+            let e = RoundError::InvalidState(round.get_state());
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                RestApi::failed(e.msg_en(), e.msg_cn()),
+            );
+        }
+    }
     let res = Track::delete(&app_state.pool, id).await;
 
     if let Ok(r) = res {
         log_info!("解除登记成功");
+        AuditLog::new(&user_info, "delete", "track", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, RestApi::successful(r.rows_affected()))
     } else {
         log_info!("解除登记失败");
@@ -791,19 +1617,28 @@ async fn userid_clans(
     (StatusCode::OK, Json(clans))
 }
 
+/// # 首领关联
+/// 仅管理员直接关联，首领走归属验证
 async fn insert_cu(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<ClanUser>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
 
     if let Ok(r) = data.insert(&app_state.pool).await {
+        AuditLog::new(&user_info, "insert", "clan_user", &header_map)
+            .entity_id(data.clan_id)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
@@ -813,22 +1648,325 @@ async fn insert_cu(
 async fn delete_cu(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<ClanUser>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, Json::default());
-    }
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, Json::default());
+        }
+    };
     // ********************鉴权********************
 
     if let Ok(r) = data.delete(&app_state.pool).await {
+        AuditLog::new(&user_info, "delete", "clan_user", &header_map)
+            .entity_id(data.clan_id)
+            .before(&data)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
     }
 }
 
+/// # 加盟申请列表
+async fn clan_applies(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ClanApply::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn clan_apply(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ClanApply::select(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 提交加盟申请
+/// 公开接口，提交时拉取部落接口数据
+async fn clan_apply_insert(
+    State(app_state): State<AppState>,
+    Json(mut data): Json<ClanApply>,
+) -> impl IntoResponse {
+    if data.contact_name.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            RestApi::failed("Contact is required", "联系人必填"),
+        );
+    }
+    if !data.load_api().await {
+        return (
+            StatusCode::GONE,
+            RestApi::failed("Clan not found in game", "游戏内未找到该部落"),
+        );
+    }
+    if data.select_pending(&app_state.pool).await.is_ok() {
+        return (
+            StatusCode::CONFLICT,
+            RestApi::failed("Application already pending", "该部落已有待审核申请"),
+        );
+    }
+
+    if let Err(e) = data.insert(&app_state.pool).await {
+        log_error!("Clan Apply {e}");
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Insert Failed", "添加失败"),
+        );
+    }
+    log_info!("{}", &data);
+    (
+        StatusCode::OK,
+        RestApi::new(
+            "Application submitted, please wait for review",
+            "申请已提交，请等待审核",
+            Some(vec![data]),
+        )
+        .builder(),
+    )
+}
+
+/// # 审核加盟申请
+async fn clan_apply_review(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<ApplyReview>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let apply = if let Ok(apply) = ClanApply::select(&app_state.pool, id).await {
+        apply
+    } else {
+        return (
+            StatusCode::GONE,
+            RestApi::failed("Application not found", "申请不存在"),
+        );
+    };
+
+    let res = apply
+        .clone()
+        .review(&app_state.pool, data, user_info.get_id())
+        .await;
+    let (status, rest_api) = match res {
+        Ok(ApplyResponse::Approved(linked)) => {
+            let msg = if linked {
+                ("Approved", "审核通过")
+            } else {
+                (
+                    "Approved, leader must verify clan ownership",
+                    "审核通过，首领须完成归属验证",
+                )
+            };
+            (
+                StatusCode::OK,
+                RestApi::new(msg.0, msg.1, Some(id)).builder(),
+            )
+        }
+        Ok(ApplyResponse::Rejected) => (StatusCode::OK, RestApi::failed("Rejected", "已拒绝")),
+        Ok(ApplyResponse::NotPending) => (
+            StatusCode::CONFLICT,
+            RestApi::failed("Application already reviewed", "申请已审核"),
+        ),
+        Ok(ApplyResponse::ClanExists) => (
+            StatusCode::CONFLICT,
+            RestApi::failed("Clan already exists", "部落已存在"),
+        ),
+        Ok(ApplyResponse::ClanRestricted) => (
+            StatusCode::CONFLICT,
+            RestApi::failed("Clan is blacklisted or locked", "部落在黑名单或锁定中"),
+        ),
+        Ok(ApplyResponse::ReasonRequired) => (
+            StatusCode::BAD_REQUEST,
+            RestApi::failed("Reason required", "拒绝需填写原因"),
+        ),
+        Err(e) => {
+            log_error!("Clan Apply {e}");
+            return (StatusCode::UNPROCESSABLE_ENTITY, RestApi::error());
+        }
+    };
+
+    if status == StatusCode::OK {
+        AuditLog::new(&user_info, "review", "clan_apply", &header_map)
+            .entity_id(id)
+            .before(&apply)
+            .after(&ClanApply::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+    }
+    (status, rest_api)
+}
+
+/// # 本人归属验证记录
+async fn clan_verifies(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let res = ClanVerify::select_user(&app_state.pool, user_info.get_id()).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 申请归属验证
+/// 返回验证码，写入游戏内部落简介后提交校验
+async fn clan_verify_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<ClanVerifyNew>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let is_global = data.is_global.unwrap_or(true);
+    let clan = if let Ok(clan) = Clan::select_tag(&app_state.pool, &data.tag, is_global).await {
+        clan
+    } else {
+        return (
+            StatusCode::GONE,
+            RestApi::failed("Clan not found", "部落不存在"),
+        );
+    };
+
+    let verify = ClanVerify::new(clan.get_id(), user_info.get_id());
+    if verify.insert(&app_state.pool).await.is_err() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Insert Failed", "添加失败"),
+        );
+    }
+    AuditLog::new(&user_info, "insert", "clan_verify", &header_map)
+        .entity_id(clan.get_id())
+        .after(&verify)
+        .record(&app_state.pool)
+        .await;
+    log_info!("{}", &verify);
+    (
+        StatusCode::OK,
+        RestApi::new(
+            "Put the code in the clan description, then submit verification",
+            "请将验证码写入部落简介后提交验证",
+            Some(vec![verify]),
+        )
+        .builder(),
+    )
+}
+
+/// # 提交归属验证
+async fn clan_verify_check(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let verify = if let Ok(verify) = ClanVerify::select(&app_state.pool, id).await {
+        verify
+    } else {
+        return (
+            StatusCode::GONE,
+            RestApi::failed("Verification not found", "验证记录不存在"),
+        );
+    };
+
+    match verify.verify(&app_state.pool, user_info.get_id()).await {
+        Ok(VerifyResponse::Verified) => {
+            AuditLog::new(&user_info, "verify", "clan_verify", &header_map)
+                .entity_id(verify.clan_id)
+                .before(&verify)
+                .after(&ClanVerify::select(&app_state.pool, id).await.ok())
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(verify.clan_id))
+        }
+        Ok(VerifyResponse::NotOwner) => (StatusCode::UNAUTHORIZED, RestApi::unauthorized()),
+        Ok(VerifyResponse::Expired) => (
+            StatusCode::GONE,
+            RestApi::failed("Verification code expired", "验证码已过期"),
+        ),
+        Ok(VerifyResponse::CodeNotFound) => (
+            StatusCode::CONFLICT,
+            RestApi::failed(
+                "Code not found in clan description",
+                "部落简介中未找到验证码",
+            ),
+        ),
+        Err(e) => {
+            log_error!("Clan Verify {e}");
+            (StatusCode::UNPROCESSABLE_ENTITY, RestApi::error())
+        }
+    }
+}
+
 async fn clan_point_all(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
@@ -872,15 +2010,31 @@ async fn clan_point(
 async fn clan_reward_point(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<OperateLog>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
-    }
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
     // ********************鉴权********************
 
+    // 检查轮次状态
+    let round = Round::select(&app_state.pool, data.round_id)
+        .await
+        .unwrap_or_default();
+    if !round.get_state().can_adjust() {
+        let e = RoundError::InvalidState(round.get_state());
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed(e.msg_en(), e.msg_cn()),
+        );
+    }
+
     // 查询重复
     let check_tr_round = Track::select_clan_round(&app_state.pool, data.clan_id, data.round_id)
         .await
@@ -927,8 +2081,15 @@ async fn clan_reward_point(
         }
     }
 
-    let res = data.new_reward(&app_state.pool).await;
+    let before = ClanPoint::select(&app_state.pool, data.clan_id).await.ok();
+    let res = data.clone().new_reward(&app_state.pool).await;
     if let Ok(r) = res {
+        AuditLog::new(&user_info, "reward", "clan_point", &header_map)
+            .entity_id(data.clan_id)
+            .before(&before)
+            .after(&ClanPoint::select(&app_state.pool, data.clan_id).await.ok())
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, RestApi::successful(r.rows_affected()))
     } else {
         (
@@ -938,12 +2099,351 @@ async fn clan_reward_point(
     }
 }
 
+async fn eligibility_rules(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = EligibilityRule::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn eligibility_rule_update(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<EligibilityRule>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = EligibilityRule::select_all(&app_state.pool)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|r| r.rule_type == data.rule_type);
+    if let Ok(r) = data.upsert(&app_state.pool).await {
+        AuditLog::new(&user_info, "update", "eligibility_rule", &header_map)
+            .before(&before)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Update Failed", "更新失败"),
+        )
+    }
+}
+
+/// # 手动准入检查
+/// 发布对战前执行，定时任务也会每小时执行
+async fn eligibility_check(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    match ClanLock::check_all(&app_state.pool).await {
+        Ok(report) => {
+            AuditLog::new(&user_info, "check", "clan_lock", &header_map)
+                .after(&report)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(report))
+        }
+        Err(e) => {
+            log_error!("Eligibility {e}");
+            (StatusCode::UNPROCESSABLE_ENTITY, RestApi::error())
+        }
+    }
+}
+
+/// # 部落锁定原因
+/// 部落首领或管理员可查
+async fn clan_locks(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    let clan_user = ClanUser {
+        clan_id: id,
+        user_id: user_info.get_id(),
+    };
+    if !user_info.check_role("admin") && clan_user.select(&app_state.pool).await.is_err() {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ClanLock::select_history(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn blacklists(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Blacklist::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 黑名单详情
+/// 被拉黑部落首领或管理员可查
+async fn blacklist(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let blacklist = if let Ok(blacklist) = Blacklist::select(&app_state.pool, id).await {
+        blacklist
+    } else {
+        return (StatusCode::GONE, RestApi::error());
+    };
+    let clan_user = ClanUser {
+        clan_id: blacklist.clan_id,
+        user_id: user_info.get_id(),
+    };
+    if !user_info.check_role("admin") && clan_user.select(&app_state.pool).await.is_err() {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    (StatusCode::OK, RestApi::successful(vec![blacklist]))
+}
+
+async fn blacklist_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<Blacklist>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    if data.reason.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            RestApi::failed("Reason required", "需填写拉黑原因"),
+        );
+    }
+    match data.insert(&app_state.pool, user_info.get_id()).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "insert", "blacklist", &header_map)
+                .entity_id(data.clan_id)
+                .after(
+                    &Blacklist::select_clan(&app_state.pool, data.clan_id)
+                        .await
+                        .ok(),
+                )
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(sqlx::Error::RowNotFound) => (
+            StatusCode::CONFLICT,
+            RestApi::failed("Clan already blacklisted", "部落已在黑名单中"),
+        ),
+        Err(e) => {
+            log_error!("Blacklist {e}");
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                RestApi::failed("Insert Failed", "添加失败"),
+            )
+        }
+    }
+}
+
+async fn blacklist_release(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Blacklist::select(&app_state.pool, id).await;
+    let res = match &before {
+        Ok(blacklist) => blacklist.release(&app_state.pool).await,
+        Err(_) => Err(sqlx::Error::RowNotFound),
+    };
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "release", "blacklist", &header_map)
+            .entity_id(id)
+            .before(&before.ok())
+            .after(&Blacklist::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::GONE,
+            RestApi::failed("Blacklist not active", "黑名单不存在或已解除"),
+        )
+    }
+}
+
+/// # 提交申诉
+/// 被拉黑部落首领提交
+async fn blacklist_appeal(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(text): Json<String>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let blacklist = match Blacklist::select(&app_state.pool, id).await {
+        Ok(blacklist) if blacklist.is_active() => blacklist,
+        _ => {
+            return (
+                StatusCode::GONE,
+                RestApi::failed("Blacklist not active", "黑名单不存在或已解除"),
+            );
+        }
+    };
+    let clan_user = ClanUser {
+        clan_id: blacklist.clan_id,
+        user_id: user_info.get_id(),
+    };
+    if clan_user.select(&app_state.pool).await.is_err() {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+
+    if let Ok(r) = blacklist.appeal(&app_state.pool, &text).await {
+        AuditLog::new(&user_info, "appeal", "blacklist", &header_map)
+            .entity_id(id)
+            .before(&blacklist)
+            .after(&Blacklist::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (StatusCode::UNPROCESSABLE_ENTITY, RestApi::error())
+    }
+}
+
+async fn blacklist_appeal_review(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(data): Json<AppealReview>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let blacklist = if let Ok(blacklist) = Blacklist::select(&app_state.pool, id).await {
+        blacklist
+    } else {
+        return (StatusCode::GONE, RestApi::error());
+    };
+
+    if let Ok(r) = blacklist.appeal_review(&app_state.pool, &data).await {
+        AuditLog::new(&user_info, "appeal_review", "blacklist", &header_map)
+            .entity_id(id)
+            .before(&blacklist)
+            .after(&Blacklist::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::CONFLICT,
+            RestApi::failed("No pending appeal", "没有待审核的申诉"),
+        )
+    }
+}
+
 async fn operate_logs(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*operate*log*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::OperateLogSelect).await {
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
@@ -962,7 +2462,7 @@ async fn operate_logs_page(
     Path((page, page_size)): Path<(i64, i64)>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if !token.eq("cfa*operate*log*select") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::OperateLogSelect).await {
         return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
     }
     // ********************鉴权********************
@@ -978,3 +2478,215 @@ async fn operate_logs_page(
         (StatusCode::GONE, RestApi::error())
     }
 }
+
+/// # 玩家黑名单列表
+async fn player_blacklists(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = PlayerBlacklist::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 拉黑玩家
+async fn player_blacklist_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<PlayerBlacklist>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    match data.insert(&app_state.pool, user_info.get_id()).await {
+        Ok(r) => {
+            AuditLog::new(&user_info, "insert", "player_blacklist", &header_map)
+                .after(&data)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        Err(sqlx::Error::RowNotFound) => (
+            StatusCode::CONFLICT,
+            RestApi::failed("Player already blacklisted", "玩家已在黑名单中"),
+        ),
+        Err(e) => {
+            log_error!("Player Blacklist {e}");
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                RestApi::failed("Insert Failed", "添加失败"),
+            )
+        }
+    }
+}
+
+/// # 解除玩家黑名单
+async fn player_blacklist_release(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = PlayerBlacklist::select(&app_state.pool, id).await.ok();
+    match PlayerBlacklist::release(&app_state.pool, id).await {
+        Ok(r) if r.rows_affected() > 0 => {
+            AuditLog::new(&user_info, "release", "player_blacklist", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&PlayerBlacklist::select(&app_state.pool, id).await.ok())
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        _ => (
+            StatusCode::GONE,
+            RestApi::failed("Blacklist not active", "黑名单不存在或已解除"),
+        ),
+    }
+}
+
+/// # 黑名单玩家出战提醒
+async fn player_alerts(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = PlayerAlert::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 提醒标记已处理
+async fn player_alert_handle(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    match PlayerAlert::handle(&app_state.pool, id, user_info.get_id()).await {
+        Ok(r) if r.rows_affected() > 0 => {
+            AuditLog::new(&user_info, "handle", "player_alert", &header_map)
+                .entity_id(id)
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        _ => (
+            StatusCode::GONE,
+            RestApi::failed("Alert not pending", "提醒不存在或已处理"),
+        ),
+    }
+}
+
+/// # 单场对战统计
+async fn war_stat(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = WarStat::select_track(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 轮次执行情况
+/// 指定结果与实际结果对比
+async fn war_compliance(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = WarStat::compliance(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 玩家历史进攻
+async fn player_attacks(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(tag): Path<PlayerTag>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if let Err(e) = UserInfo::get_user(&token).await {
+        log_warn!("UNAUTHORIZED {e}");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = WarAttack::select_player(&app_state.pool, &tag).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
@@ -2,7 +2,8 @@ use crate::orange::clan_point::ClanPoint;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{
-    Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, query_scalar,
+    Error, FromRow, PgConnection, PgExecutor, Pool, Postgres, postgres::PgQueryResult, query,
+    query_as, query_scalar,
 };
 use uuid::Uuid;
 
@@ -24,6 +25,9 @@ pub struct OperateLog {
     #[serde(skip_deserializing)]
     pub round_code: String,
     pub remarks: Option<String>,
+    /// # 奖励券实际变动
+    #[serde(skip_deserializing)]
+    pub amount: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -36,7 +40,43 @@ pub enum RewardType {
     Penalty3,  // 处罚3
 }
 
+impl RewardType {
+    /// # 奖励券变动
+    pub fn amount(&self) -> i64 {
+        match self {
+            RewardType::HitExternal | RewardType::FaceBlack => 1,
+            RewardType::Penalty => -1,
+            RewardType::Penalty2 => -2,
+            RewardType::Penalty3 => -3,
+        }
+    }
+
+    fn text(&self) -> String {
+        let amount = self.amount();
+        match self {
+            RewardType::HitExternal => format!("打虫奖励+{amount}"),
+            RewardType::FaceBlack => format!("脸黑安慰+{amount}"),
+            _ => format!("违规处罚{amount}"),
+        }
+    }
+}
+
 impl OperateLog {
+    pub fn new(
+        round_id: Uuid,
+        clan_id: Uuid,
+        reward_type: RewardType,
+        remarks: Option<String>,
+    ) -> Self {
+        Self {
+            round_id,
+            clan_id,
+            reward_type,
+            remarks,
+            ..Default::default()
+        }
+    }
+
     pub fn is_reward_penalty(&self) -> bool {
         match &self.reward_type {
             RewardType::Penalty | RewardType::Penalty2 | RewardType::Penalty3 => true,
@@ -66,6 +106,14 @@ impl OperateLog {
             .unwrap_or_default()
     }
 
+    /// # 轮次奖惩记录
+    pub async fn select_round(pool: &Pool<Postgres>, round_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select o.*, c.tag, c.name, r.code round_code from orange.operate_log o, orange.round r, orange.clan c where o.round_id = r.id and o.clan_id = c.id and o.round_id = $1 order by o.create_time")
+            .bind(round_id)
+            .fetch_all(pool)
+            .await
+    }
+
     pub async fn select_clan_round(&self, pool: &Pool<Postgres>) -> Result<Self, Error> {
         query_as("select * from orange.operate_log where clan_id = $1 and round_id = $2")
             .bind(&self.clan_id)
@@ -74,64 +122,45 @@ impl OperateLog {
             .await
     }
 
-    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
-        query("insert into orange.operate_log values (DEFAULT, $1, $2, $3, $4, $5)")
+    pub async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.operate_log values (DEFAULT, $1, $2, $3, $4, $5, $6)")
             .bind(&self.round_id)
             .bind(&self.text)
             .bind(Utc::now())
             .bind(&self.clan_id)
             .bind(&self.remarks)
-            .execute(pool)
+            .bind(self.amount)
+            .execute(executor)
             .await
     }
 
-    pub async fn new_reward(mut self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
-        let clan_point = ClanPoint::select(pool, self.clan_id).await?;
-        let text = match self.reward_type.clone() {
-            RewardType::HitExternal => {
-                if let Ok(q) = clan_point.update_reward_point(pool, 1).await {
-                    q.rows_affected()
-                } else {
-                    0
-                };
-                "打虫奖励+1".to_string()
-            }
-            RewardType::FaceBlack => {
-                if let Ok(q) = clan_point.update_reward_point(pool, 1).await {
-                    q.rows_affected()
-                } else {
-                    0
-                };
-                "脸黑安慰+1".to_string()
-            }
-            RewardType::Penalty => {
-                if let Ok(q) = clan_point.update_reward_point(pool, -1).await {
-                    q.rows_affected()
-                } else {
-                    0
-                };
-                "违规处罚-1".to_string()
-            }
-            RewardType::Penalty2 => {
-                if let Ok(q) = clan_point.update_reward_point(pool, -2).await {
-                    q.rows_affected()
-                } else {
-                    0
-                };
-                "违规处罚-2".to_string()
-            }
-            RewardType::Penalty3 => {
-                if let Ok(q) = clan_point.update_reward_point(pool, -3).await {
-                    q.rows_affected()
-                } else {
-                    0
-                };
-                "违规处罚-3".to_string()
-            }
-        };
+    pub async fn new_reward(self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let mut tx = pool.begin().await?;
+        let res = self.new_reward_in(&mut tx).await?;
+        tx.commit().await?;
+        Ok(res)
+    }
+
+    /// # 奖惩
+    /// 奖励券和记录同一事务
+    pub async fn new_reward_in(mut self, conn: &mut PgConnection) -> Result<PgQueryResult, Error> {
+        let clan_point = ClanPoint::select(&mut *conn, self.clan_id).await?;
+        // 记录实际变动，超出上限未加的记为0
+        let amount = self.reward_type.amount();
+        clan_point.update_reward_point(&mut *conn, amount).await?;
+        self.amount = clan_point.reward_add(amount);
+        let text = self.reward_type.text();
         self.text = Option::from(text);
         self.create_time = Utc::now();
         // 写入
-        self.insert(pool).await
+        self.insert(conn).await
     }
 }
+
+#[test]
+fn test_reward_text() {
+    assert_eq!(RewardType::HitExternal.text(), "打虫奖励+1");
+    assert_eq!(RewardType::FaceBlack.text(), "脸黑安慰+1");
+    assert_eq!(RewardType::Penalty3.text(), "违规处罚-3");
+    assert_eq!(RewardType::Penalty2.amount(), -2);
+}
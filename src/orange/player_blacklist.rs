@@ -0,0 +1,302 @@
+use crate::{
+    api::{self, War, WarClan},
+    core::tag::{ClanTag, PlayerTag},
+    orange::{Clan, Round},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use sqlx::{Error, FromRow, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 玩家黑名单
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct PlayerBlacklist {
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+    pub player_tag: PlayerTag,
+    pub player_name: Option<String>,
+    pub reason: String,
+    #[serde(skip_deserializing)]
+    pub issue_by: Uuid,
+    #[serde(skip_deserializing)]
+    pub start_time: DateTime<Utc>,
+    /// # 到期时间，为空永久
+    pub expire_time: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub release_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum AlertSide {
+    /// # 联盟部落阵容
+    #[default]
+    Alliance = 1,
+    /// # 对手阵容
+    Opponent = 2,
+}
+
+/// # 黑名单玩家出战提醒
+/// 每轮同一玩家在同一部落只提醒一次
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct PlayerAlert {
+    pub id: Uuid,
+    pub blacklist_id: Uuid,
+    pub round_id: Uuid,
+    /// # 发现对战的联盟部落
+    pub clan_id: Uuid,
+    pub side: AlertSide,
+    pub player_tag: PlayerTag,
+    pub player_name: Option<String>,
+    /// # 玩家所在部落
+    pub war_clan_tag: Option<ClanTag>,
+    pub war_clan_name: Option<String>,
+    pub create_time: DateTime<Utc>,
+    pub handle_by: Option<Uuid>,
+    pub handle_time: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    pub reason: Option<String>,
+}
+
+impl Display for PlayerBlacklist {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Player Blacklist {}\n - Player: {} {:?}\n - Reason: {}\n - Start: {} | Expire: {:?} | Release: {:?}",
+            self.id,
+            self.player_tag,
+            self.player_name,
+            self.reason,
+            self.start_time,
+            self.expire_time,
+            self.release_time
+        )
+    }
+}
+
+impl PlayerBlacklist {
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.player_blacklist order by start_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from orange.player_blacklist where id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 生效中的黑名单
+    pub async fn select_active(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.player_blacklist where release_time is null and (expire_time is null or expire_time > $1)")
+            .bind(Utc::now())
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 拉黑玩家
+    /// 已有生效中的黑名单返回RowNotFound，已到期的可再次拉黑
+    pub async fn insert(
+        &self,
+        pool: &Pool<Postgres>,
+        issue_by: Uuid,
+    ) -> Result<PgQueryResult, Error> {
+        let exists = query_as::<_, Self>(
+            "select * from orange.player_blacklist where player_tag = $1 and release_time is null and (expire_time is null or expire_time > $2)",
+        )
+        .bind(&self.player_tag)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await;
+        if exists.is_ok() {
+            return Err(Error::RowNotFound);
+        }
+        log_info!("拉黑玩家 {} {}", self.player_tag, self.reason);
+        query("insert into orange.player_blacklist values(DEFAULT, $1, $2, $3, $4, $5, $6, NULL)")
+            .bind(&self.player_tag)
+            .bind(&self.player_name)
+            .bind(&self.reason)
+            .bind(issue_by)
+            .bind(Utc::now())
+            .bind(self.expire_time)
+            .execute(pool)
+            .await
+    }
+
+    /// # 解除
+    pub async fn release(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        query("update orange.player_blacklist set release_time = $1 where id = $2 and release_time is null")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 对战阵容筛查
+    /// 检查联盟部落当前对战双方阵容，发现黑名单玩家写入提醒
+    pub async fn screen_wars(pool: &Pool<Postgres>) -> Result<usize, Error> {
+        let blacklist: HashMap<PlayerTag, Self> = Self::select_active(pool)
+            .await?
+            .into_iter()
+            .map(|b| (b.player_tag.clone(), b))
+            .collect();
+        if blacklist.is_empty() {
+            return Ok(0);
+        }
+        let global_round = Round::select_last(pool, true).await.ok();
+        let china_round = Round::select_last(pool, false).await.ok();
+
+        let mut count = 0;
+        for clan in Clan::select_sync(pool).await? {
+            let round = if clan.is_global.unwrap_or(true) {
+                &global_round
+            } else {
+                &china_round
+            };
+            let Some(round) = round else {
+                continue;
+            };
+            let Some(tag) = clan.tag.as_ref() else {
+                log_warn!("部落无标签 {}", clan.get_id());
+                continue;
+            };
+            let war = War::get(tag).await;
+            tokio::time::sleep(api::API_INTERVAL).await;
+            let sides = [
+                (AlertSide::Alliance, war.clan.as_ref()),
+                (AlertSide::Opponent, war.opponent.as_ref()),
+            ];
+            for (side, war_clan) in sides {
+                let Some(war_clan) = war_clan else {
+                    continue;
+                };
+                for (blacklisted, name) in Self::matches(&blacklist, war_clan) {
+                    let alert = PlayerAlert {
+                        blacklist_id: blacklisted.id,
+                        round_id: round.get_id(),
+                        clan_id: clan.get_id(),
+                        side,
+                        player_tag: blacklisted.player_tag.clone(),
+                        player_name: name,
+                        war_clan_tag: war_clan.tag.as_deref().and_then(|t| ClanTag::new(t).ok()),
+                        war_clan_name: war_clan.name.clone(),
+                        id: Uuid::default(),
+                        create_time: Utc::now(),
+                        handle_by: None,
+                        handle_time: None,
+                        reason: None,
+                    };
+                    if alert.insert(pool).await?.rows_affected() > 0 {
+                        log_warn!(
+                            "黑名单玩家出战 {} {:?} 部落 {:?} {:?}",
+                            alert.player_tag,
+                            alert.player_name,
+                            alert.war_clan_tag,
+                            side
+                        );
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// # 阵容中的黑名单玩家
+    fn matches<'a>(
+        blacklist: &'a HashMap<PlayerTag, Self>,
+        war_clan: &WarClan,
+    ) -> Vec<(&'a Self, Option<String>)> {
+        war_clan
+            .members
+            .iter()
+            .flatten()
+            .filter_map(|member| {
+                let tag = PlayerTag::new(member.tag.as_deref()?).ok()?;
+                blacklist.get(&tag).map(|b| (b, member.name.clone()))
+            })
+            .collect()
+    }
+}
+
+impl PlayerAlert {
+    /// # 提醒列表
+    /// 未处理的在前
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select a.*, b.reason from orange.player_alert a, orange.player_blacklist b where a.blacklist_id = b.id order by a.handle_time desc nulls first, a.create_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.player_alert values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, NULL, NULL)
+            on conflict (blacklist_id, round_id, clan_id) do nothing")
+            .bind(self.blacklist_id)
+            .bind(self.round_id)
+            .bind(self.clan_id)
+            .bind(self.side)
+            .bind(&self.player_tag)
+            .bind(&self.player_name)
+            .bind(&self.war_clan_tag)
+            .bind(&self.war_clan_name)
+            .bind(Utc::now())
+            .execute(pool)
+            .await
+    }
+
+    /// # 标记已处理
+    pub async fn handle(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        handle_by: Uuid,
+    ) -> Result<PgQueryResult, Error> {
+        query("update orange.player_alert set handle_by = $1, handle_time = $2 where id = $3 and handle_time is null")
+            .bind(handle_by)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+}
+
+#[test]
+fn test_matches() {
+    use crate::api::WarClanMember;
+
+    let blacklisted = PlayerBlacklist {
+        id: Uuid::default(),
+        player_tag: PlayerTag::new("#9QQ2P0V").unwrap(),
+        player_name: None,
+        reason: String::new(),
+        issue_by: Uuid::default(),
+        start_time: Utc::now(),
+        expire_time: None,
+        release_time: None,
+    };
+    let blacklist = HashMap::from([(blacklisted.player_tag.clone(), blacklisted)]);
+    let war_clan = WarClan {
+        members: Some(vec![
+            WarClanMember {
+                tag: Some("#9qq2p0v".to_string()),
+                name: Some("hopper".to_string()),
+                ..Default::default()
+            },
+            WarClanMember {
+                tag: Some("#2PP".to_string()),
+                ..Default::default()
+            },
+        ]),
+        ..Default::default()
+    };
+    let found = PlayerBlacklist::matches(&blacklist, &war_clan);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].1.as_deref(), Some("hopper"));
+}
@@ -1,9 +1,15 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use crate::orange::{Track, TrackRollback};
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use sqlx::{
-    Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, query_scalar,
+    Error, FromRow, PgExecutor, Pool, Postgres, Type, postgres::PgQueryResult, query, query_as,
+    query_scalar,
 };
+use std::fmt::{Display, Formatter};
 use uuid::Uuid;
+use void_log::log_info;
 
 #[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
 pub struct Round {
@@ -11,9 +17,261 @@ pub struct Round {
     code: String,
     round_time: DateTime<Utc>,
     create_time: DateTime<Utc>,
+    /// # 国际服/国服，两服轮次各自独立
+    is_global: bool,
+    state: RoundState,
+    /// # 登记开放时间
+    open_time: DateTime<Utc>,
+    /// # 登记截止时间
+    close_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr, Copy)]
+#[repr(i16)]
+pub enum RoundState {
+    /// # 已发布，未开放登记
+    #[default]
+    Scheduled = 0,
+    /// # 登记中
+    Open = 1,
+    /// # 登记截止
+    Closed = 2,
+    /// # 已结算
+    Settled = 3,
+    /// # 已取消
+    Cancelled = 4,
+}
+
+/// # 轮次状态变更
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundTransition {
+    pub state: RoundState,
+}
+
+/// # 发布轮次
+/// 时间可带偏移（RFC 3339），不带偏移时按时区解释
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundNew {
+    pub time: String,
+    /// # IANA时区，如 Asia/Shanghai
+    pub timezone: Option<String>,
+    /// # 是否国际服，默认国际服
+    pub is_global: Option<bool>,
+    /// # 登记开放时间，默认轮次时间
+    pub open_time: Option<String>,
+    /// # 登记截止时间，默认开放后24小时
+    pub close_time: Option<String>,
+}
+
+/// # 取消轮次报告
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundCancelReport {
+    pub round_id: Uuid,
+    pub code: String,
+    pub tracks: Vec<TrackRollback>,
+}
+
+/// # 轮次服务器筛选
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundServer {
+    pub is_global: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum RoundError {
+    InvalidTime(String),
+    InvalidTimezone(String),
+    /// # 夏令时跳过的本地时间
+    NonexistentTime(String),
+    NotFuture,
+    /// # 截止时间不晚于开放时间
+    InvalidWindow,
+    Duplicate,
+    /// # 当前状态不允许该操作
+    InvalidState(RoundState),
+    InvalidTransition(RoundState, RoundState),
+    /// # 不是所在服务器最新一轮
+    NotLatest,
+    Database(Error),
+}
+
+impl Display for RoundError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg_en())
+    }
+}
+
+impl From<Error> for RoundError {
+    fn from(value: Error) -> Self {
+        RoundError::Database(value)
+    }
+}
+
+impl RoundError {
+    pub fn msg_en(&self) -> String {
+        match self {
+            RoundError::InvalidTime(t) => format!("Invalid time: {t}"),
+            RoundError::InvalidTimezone(tz) => format!("Invalid timezone: {tz}"),
+            RoundError::NonexistentTime(t) => format!("Time does not exist in timezone: {t}"),
+            RoundError::NotFuture => "Round time must be in the future".to_string(),
+            RoundError::InvalidWindow => "Close time must be after open time".to_string(),
+            RoundError::Duplicate => "Round already published".to_string(),
+            RoundError::InvalidState(s) => format!("Round is {s:?}"),
+            RoundError::InvalidTransition(from, to) => {
+                format!("Round can not change from {from:?} to {to:?}")
+            }
+            RoundError::NotLatest => "Only the latest round can be cancelled".to_string(),
+            RoundError::Database(e) => format!("Database error: {e}"),
+        }
+    }
+
+    pub fn msg_cn(&self) -> String {
+        match self {
+            RoundError::InvalidTime(t) => format!("时间格式错误: {t}"),
+            RoundError::InvalidTimezone(tz) => format!("时区错误: {tz}"),
+            RoundError::NonexistentTime(t) => format!("该时区不存在此时间: {t}"),
+            RoundError::NotFuture => "轮次时间必须晚于当前时间".to_string(),
+            RoundError::InvalidWindow => "截止时间必须晚于开放时间".to_string(),
+            RoundError::Duplicate => "轮次已发布".to_string(),
+            RoundError::InvalidState(s) => format!("轮次{}", s.name_cn()),
+            RoundError::InvalidTransition(from, to) => {
+                format!("轮次不能从{}变为{}", from.name_cn(), to.name_cn())
+            }
+            RoundError::NotLatest => "只能取消最新一轮".to_string(),
+            RoundError::Database(e) => format!("数据库错误: {e}"),
+        }
+    }
+}
+
+impl RoundState {
+    pub fn name_cn(&self) -> &'static str {
+        match self {
+            RoundState::Scheduled => "未开放登记",
+            RoundState::Open => "登记中",
+            RoundState::Closed => "登记已截止",
+            RoundState::Settled => "已结算",
+            RoundState::Cancelled => "已取消",
+        }
+    }
+
+    /// # 状态只能逐级推进
+    /// 取消走单独流程
+    pub fn can_transition(&self, to: RoundState) -> bool {
+        to != RoundState::Cancelled && to as i16 == *self as i16 + 1
+    }
+
+    /// # 结算前都可以取消
+    pub fn can_cancel(&self) -> bool {
+        !matches!(self, RoundState::Settled | RoundState::Cancelled)
+    }
+
+    pub fn can_register(&self) -> bool {
+        *self == RoundState::Open
+    }
+
+    /// # 奖惩和解除登记在结算前可用
+    pub fn can_adjust(&self) -> bool {
+        matches!(self, RoundState::Open | RoundState::Closed)
+    }
+}
+
+impl RoundNew {
+    /// # 默认时区
+    const DEFAULT_TIMEZONE: Tz = Tz::Asia__Shanghai;
+
+    /// # 无偏移时间格式
+    const NAIVE_FORMATS: [&'static str; 3] =
+        ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"];
+
+    pub fn new(time: &str, timezone: &str) -> Self {
+        Self {
+            time: time.to_string(),
+            timezone: Some(timezone.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.is_global.unwrap_or(true)
+    }
+
+    /// # 编号前缀
+    fn prefix(&self) -> &'static str {
+        if self.is_global() { "GLOBAL" } else { "CHINA" }
+    }
+
+    fn timezone(&self) -> Result<Tz, RoundError> {
+        match &self.timezone {
+            Some(tz) => tz
+                .parse()
+                .map_err(|_| RoundError::InvalidTimezone(tz.clone())),
+            None => Ok(Self::DEFAULT_TIMEZONE),
+        }
+    }
+
+    /// # 按时区解析单个时间
+    /// 夏令时重叠时取较早的时间
+    fn parse_time(time: &str, tz: Tz) -> Result<DateTime<Utc>, RoundError> {
+        let time = time.trim();
+        if let Ok(dt) = DateTime::parse_from_rfc3339(time) {
+            return Ok(dt.to_utc());
+        }
+        let ndt = Self::NAIVE_FORMATS
+            .iter()
+            .find_map(|fmt| NaiveDateTime::parse_from_str(time, fmt).ok())
+            .ok_or_else(|| RoundError::InvalidTime(time.to_string()))?;
+        match tz.from_local_datetime(&ndt) {
+            LocalResult::Single(dt) => Ok(dt.to_utc()),
+            LocalResult::Ambiguous(earliest, _) => Ok(earliest.to_utc()),
+            LocalResult::None => Err(RoundError::NonexistentTime(time.to_string())),
+        }
+    }
+
+    /// # 解析为UTC时间和轮次编号
+    /// 编号取该时区下的日期
+    pub fn parse(&self) -> Result<(DateTime<Utc>, String), RoundError> {
+        let tz = self.timezone()?;
+        let utc_time = Self::parse_time(&self.time, tz)?;
+        let code = format!(
+            "{}{}",
+            self.prefix(),
+            utc_time.with_timezone(&tz).format("%Y%m%d")
+        );
+        Ok((utc_time, code))
+    }
+
+    /// # 登记窗口
+    pub fn window(
+        &self,
+        round_time: DateTime<Utc>,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), RoundError> {
+        let tz = self.timezone()?;
+        let open_time = match &self.open_time {
+            Some(t) => Self::parse_time(t, tz)?,
+            None => round_time,
+        };
+        let close_time = match &self.close_time {
+            Some(t) => Self::parse_time(t, tz)?,
+            None => open_time + Round::REGISTRATION_WINDOW,
+        };
+        if close_time <= open_time {
+            return Err(RoundError::InvalidWindow);
+        }
+        Ok((open_time, close_time))
+    }
+}
+
+impl RoundServer {
+    /// # 未指定时默认国际服
+    pub fn is_global(&self) -> bool {
+        self.is_global.unwrap_or(true)
+    }
 }
 
 impl Round {
+    /// # 默认登记时长
+    const REGISTRATION_WINDOW: Duration = Duration::hours(24);
+
     pub fn get_id(&self) -> Uuid {
         self.id
     }
@@ -22,12 +280,16 @@ impl Round {
         &self.code
     }
 
-    pub fn get_create_time(&self) -> DateTime<Utc> {
-        self.create_time
+    pub fn get_round_time(&self) -> DateTime<Utc> {
+        self.round_time
     }
 
-    pub async fn check_not_now(&self) -> bool {
-        self.round_time > Utc::now()
+    pub fn is_global(&self) -> bool {
+        self.is_global
+    }
+
+    pub fn get_state(&self) -> RoundState {
+        self.state
     }
 
     pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
@@ -55,42 +317,281 @@ impl Round {
             .unwrap_or_default()
     }
 
-    pub async fn select_last(pool: &Pool<Postgres>) -> Result<Self, Error> {
-        query_as("select * from orange.round order by create_time desc limit 1")
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from orange.round where id = $1")
+            .bind(id)
             .fetch_one(pool)
             .await
     }
 
-    pub async fn select_last2(pool: &Pool<Postgres>) -> Self {
-        let last2 =
-            query_as::<_, Self>("select * from orange.round order by create_time desc limit 2")
-                .fetch_all(pool)
-                .await
-                .unwrap();
-        if let Some(l) = last2.last() {
-            l.clone()
-        } else {
-            Round::default()
+    /// # 同服务器上一轮
+    pub async fn select_previous(pool: &Pool<Postgres>, round: &Round) -> Result<Self, Error> {
+        query_as(
+            "select * from orange.round where create_time < $1 and is_global = $2 and state <> 4 order by create_time desc limit 1",
+        )
+        .bind(round.create_time)
+        .bind(round.is_global)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// # 服务器最新轮次
+    /// 跳过已取消的轮次
+    pub async fn select_last(
+        executor: impl PgExecutor<'_>,
+        is_global: bool,
+    ) -> Result<Self, Error> {
+        query_as(
+            "select * from orange.round where is_global = $1 and state <> 4 order by create_time desc limit 1",
+        )
+        .bind(is_global)
+        .fetch_one(executor)
+        .await
+    }
+
+    /// # 服务器登记中的轮次
+    /// 下一轮已发布时仍取本轮
+    pub async fn select_open(pool: &Pool<Postgres>, is_global: bool) -> Result<Self, Error> {
+        query_as(
+            "select * from orange.round where is_global = $1 and state = $2 order by create_time desc limit 1",
+        )
+        .bind(is_global)
+        .bind(RoundState::Open)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// # 发布轮次
+    /// 时间须晚于当前，且同服务器下编号和时间都不能与未取消的轮次重复
+    pub async fn insert(
+        round_new: &RoundNew,
+        pool: &Pool<Postgres>,
+    ) -> Result<PgQueryResult, RoundError> {
+        let (utc_time, code) = round_new.parse()?;
+        if utc_time <= Utc::now() {
+            return Err(RoundError::NotFuture);
+        }
+        let (open_time, close_time) = round_new.window(utc_time)?;
+        let exists: i64 = query_scalar(
+            "select count(id) from orange.round where is_global = $3 and state <> 4 and (code = $1 or round_time = $2)",
+        )
+        .bind(&code)
+        .bind(utc_time)
+        .bind(round_new.is_global())
+        .fetch_one(pool)
+        .await?;
+        if exists > 0 {
+            return Err(RoundError::Duplicate);
         }
+
+        Ok(
+            query("insert into orange.round values(DEFAULT, $1, $2, $3, $4, $5, $6, $7)")
+                .bind(code)
+                .bind(utc_time)
+                .bind(Utc::now())
+                .bind(round_new.is_global())
+                .bind(RoundState::Scheduled)
+                .bind(open_time)
+                .bind(close_time)
+                .execute(pool)
+                .await?,
+        )
     }
 
-    pub async fn insert(time_str: &str, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
-        let ndt = if let Ok(naive_date_time) =
-            NaiveDateTime::parse_from_str(time_str, "%Y-%m-%dT%H:%M:%S")
-        {
-            naive_date_time
-        } else {
-            NaiveDateTime::parse_from_str(time_str, "%Y-%m-%dT%H:%M").unwrap()
+    /// # 改期
+    /// 仅未开放登记的轮次可改，服务器不变
+    pub async fn reschedule(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        round_new: &RoundNew,
+    ) -> Result<PgQueryResult, RoundError> {
+        let round = Self::select(pool, id).await?;
+        if round.state != RoundState::Scheduled {
+            return Err(RoundError::InvalidState(round.state));
+        }
+        let round_new = RoundNew {
+            is_global: Some(round.is_global),
+            ..round_new.clone()
         };
-        let local_time = Local.from_local_datetime(&ndt).single().unwrap();
-        let utc_time = local_time.to_utc().with_timezone(&Utc);
-        let code = ndt.format("GLOBAL%Y%m%d").to_string();
-
-        query("insert into orange.round values(DEFAULT, $1, $2, $3)")
-            .bind(code)
-            .bind(utc_time)
-            .bind(Utc::now())
-            .execute(pool)
-            .await
+        let (utc_time, code) = round_new.parse()?;
+        if utc_time <= Utc::now() {
+            return Err(RoundError::NotFuture);
+        }
+        let (open_time, close_time) = round_new.window(utc_time)?;
+        let exists: i64 = query_scalar(
+            "select count(id) from orange.round where is_global = $3 and id <> $4 and state <> 4 and (code = $1 or round_time = $2)",
+        )
+        .bind(&code)
+        .bind(utc_time)
+        .bind(round.is_global)
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+        if exists > 0 {
+            return Err(RoundError::Duplicate);
+        }
+
+        Ok(query(
+            "update orange.round set code = $1, round_time = $2, open_time = $3, close_time = $4 where id = $5 and state = $6",
+        )
+        .bind(code)
+        .bind(utc_time)
+        .bind(open_time)
+        .bind(close_time)
+        .bind(id)
+        .bind(RoundState::Scheduled)
+        .execute(pool)
+        .await?)
+    }
+
+    /// # 取消轮次
+    /// 只能取消所在服务器最新一轮，按登记倒序撤销全部对局
+    /// 撤销和状态变更同一事务，任一失败整轮不变
+    pub async fn cancel(pool: &Pool<Postgres>, id: Uuid) -> Result<RoundCancelReport, RoundError> {
+        let mut tx = pool.begin().await?;
+        // 锁定轮次，撤销期间的新登记须等待提交
+        let round: Self = query_as("select * from orange.round where id = $1 for update")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if !round.state.can_cancel() {
+            return Err(RoundError::InvalidState(round.state));
+        }
+        if Self::select_last(&mut *tx, round.is_global).await?.id != id {
+            return Err(RoundError::NotLatest);
+        }
+
+        let mut tracks = vec![];
+        for track in Track::select_round(&mut *tx, id).await? {
+            tracks.push(track.rollback(&mut tx).await?);
+        }
+        query("update orange.round set state = $1 where id = $2")
+            .bind(RoundState::Cancelled)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        log_info!("取消轮次 {} 撤销对局 {}", round.code, tracks.len());
+        Ok(RoundCancelReport {
+            round_id: id,
+            code: round.code,
+            tracks,
+        })
+    }
+
+    /// # 按登记窗口推进状态
+    /// 到开放时间开放，到截止时间截止；结算由管理员操作
+    pub async fn advance_all(pool: &Pool<Postgres>) -> Result<u64, Error> {
+        let now = Utc::now();
+        let opened =
+            query("update orange.round set state = $1 where state = $2 and open_time <= $3")
+                .bind(RoundState::Open)
+                .bind(RoundState::Scheduled)
+                .bind(now)
+                .execute(pool)
+                .await?
+                .rows_affected();
+        let closed =
+            query("update orange.round set state = $1 where state = $2 and close_time <= $3")
+                .bind(RoundState::Closed)
+                .bind(RoundState::Open)
+                .bind(now)
+                .execute(pool)
+                .await?
+                .rows_affected();
+        Ok(opened + closed)
+    }
+
+    /// # 管理员变更状态
+    pub async fn transition(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        to: RoundState,
+    ) -> Result<PgQueryResult, RoundError> {
+        let round = Self::select(pool, id).await?;
+        if !round.state.can_transition(to) {
+            return Err(RoundError::InvalidTransition(round.state, to));
+        }
+        Ok(
+            query("update orange.round set state = $1 where id = $2 and state = $3")
+                .bind(to)
+                .bind(id)
+                .bind(round.state)
+                .execute(pool)
+                .await?,
+        )
     }
 }
+
+#[test]
+fn test_round_parse() {
+    let (time, code) = RoundNew::new("2025-03-09T20:00", "Asia/Shanghai")
+        .parse()
+        .unwrap();
+    assert_eq!(time.to_rfc3339(), "2025-03-09T12:00:00+00:00");
+    assert_eq!(code, "GLOBAL20250309");
+
+    // 偏移时间按所给时区取日期
+    let (time, code) = RoundNew::new("2025-03-09T23:30:00Z", "Asia/Shanghai")
+        .parse()
+        .unwrap();
+    assert_eq!(time.to_rfc3339(), "2025-03-09T23:30:00+00:00");
+    assert_eq!(code, "GLOBAL20250310");
+
+    // 国服轮次
+    let round_new = RoundNew {
+        is_global: Some(false),
+        ..RoundNew::new("2025-03-09T20:00", "Asia/Shanghai")
+    };
+    assert_eq!(round_new.parse().unwrap().1, "CHINA20250309");
+
+    // 夏令时前后
+    let (time, _) = RoundNew::new("2025-03-08T20:00", "America/New_York")
+        .parse()
+        .unwrap();
+    assert_eq!(time.to_rfc3339(), "2025-03-09T01:00:00+00:00");
+    let (time, _) = RoundNew::new("2025-03-09T20:00", "America/New_York")
+        .parse()
+        .unwrap();
+    assert_eq!(time.to_rfc3339(), "2025-03-10T00:00:00+00:00");
+    assert!(matches!(
+        RoundNew::new("2025-03-09T02:30", "America/New_York").parse(),
+        Err(RoundError::NonexistentTime(_))
+    ));
+
+    assert!(matches!(
+        RoundNew::new("09/03/2025", "Asia/Shanghai").parse(),
+        Err(RoundError::InvalidTime(_))
+    ));
+    assert!(matches!(
+        RoundNew::new("2025-03-09T20:00", "Mars/Base").parse(),
+        Err(RoundError::InvalidTimezone(_))
+    ));
+}
+
+#[test]
+fn test_round_lifecycle() {
+    let round_new = RoundNew::new("2025-03-09T20:00", "Asia/Shanghai");
+    let (time, _) = round_new.parse().unwrap();
+    let (open_time, close_time) = round_new.window(time).unwrap();
+    assert_eq!(open_time, time);
+    assert_eq!(close_time, time + Duration::hours(24));
+
+    let round_new = RoundNew {
+        open_time: Some("2025-03-09T21:00".to_string()),
+        close_time: Some("2025-03-09T21:00".to_string()),
+        ..round_new
+    };
+    assert!(matches!(
+        round_new.window(time),
+        Err(RoundError::InvalidWindow)
+    ));
+
+    assert!(RoundState::Scheduled.can_transition(RoundState::Open));
+    assert!(RoundState::Closed.can_transition(RoundState::Settled));
+    assert!(!RoundState::Scheduled.can_transition(RoundState::Closed));
+    assert!(!RoundState::Settled.can_transition(RoundState::Open));
+    assert!(!RoundState::Settled.can_transition(RoundState::Cancelled));
+    assert!(RoundState::Closed.can_cancel());
+    assert!(!RoundState::Settled.can_cancel());
+}
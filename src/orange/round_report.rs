@@ -0,0 +1,197 @@
+use crate::orange::{Clan, Round, Track, TrackResult, TrackType, operate_log::OperateLog};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, Pool, Postgres, query_as};
+use uuid::Uuid;
+use void_log::log_info;
+
+/// # 按类型统计
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct TypeCount {
+    pub r#type: TrackType,
+    pub count: i64,
+}
+
+/// # 按结果统计
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ResultCount {
+    pub result: TrackResult,
+    pub count: i64,
+}
+
+/// # 部落本轮积分变动
+/// 取本轮第一场登记前和最后一场登记后的积分
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct PointMove {
+    pub clan_id: Uuid,
+    pub tag: Option<String>,
+    pub name: Option<String>,
+    pub point_before: i64,
+    pub point_after: i64,
+    pub delta: i64,
+}
+
+/// # 单轮统计
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundSummary {
+    pub round_id: Uuid,
+    pub code: String,
+    pub tracks: i64,
+    pub registered_clans: i64,
+    pub by_type: Vec<TypeCount>,
+    pub by_result: Vec<ResultCount>,
+    /// # 同服务器未登记的正常部落
+    pub unregistered: Vec<Clan>,
+    pub point_moves: Vec<PointMove>,
+    /// # 对局消耗的奖励券
+    pub tickets_used: i64,
+    /// # 对局消耗的处罚券
+    pub penalties_used: i64,
+    /// # 本轮发放的奖励券
+    pub tickets_issued: i64,
+    /// # 本轮开出的处罚券
+    pub penalties_issued: i64,
+    pub rewards: Vec<OperateLog>,
+}
+
+/// # 与上一轮对比
+/// 本轮减上一轮
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundComparison {
+    pub previous_round_id: Uuid,
+    pub previous_code: String,
+    pub tracks: i64,
+    pub registered_clans: i64,
+    pub unregistered: i64,
+    pub tickets_used: i64,
+    pub tickets_issued: i64,
+}
+
+/// # 轮次报告
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RoundReport {
+    #[serde(flatten)]
+    pub summary: RoundSummary,
+    pub comparison: Option<RoundComparison>,
+}
+
+impl RoundSummary {
+    async fn by_type(pool: &Pool<Postgres>, round_id: Uuid) -> Result<Vec<TypeCount>, Error> {
+        query_as("select type, count(*) count from orange.track where round_id = $1 group by type order by type")
+            .bind(round_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn by_result(pool: &Pool<Postgres>, round_id: Uuid) -> Result<Vec<ResultCount>, Error> {
+        query_as("select result, count(*) count from orange.track where round_id = $1 group by result order by result desc")
+            .bind(round_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn unregistered(pool: &Pool<Postgres>, round: &Round) -> Result<Vec<Clan>, Error> {
+        query_as(
+            "select * from orange.clan c
+            where c.status = 1 and c.deleted_at is null and coalesce(c.is_global, true) = $2
+            and not exists (select 1 from orange.track t where t.round_id = $1 and (t.self_clan_id = c.id or t.rival_clan_id = c.id))
+            order by c.tag",
+        )
+        .bind(round.get_id())
+        .bind(round.is_global())
+        .fetch_all(pool)
+        .await
+    }
+
+    async fn point_moves(pool: &Pool<Postgres>, round_id: Uuid) -> Result<Vec<PointMove>, Error> {
+        query_as(
+            "select p.*, p.point_after - p.point_before delta from (
+                select m.clan_id, c.tag, c.name,
+                (array_agg(m.history order by m.create_time))[1] point_before,
+                (array_agg(m.now order by m.create_time desc))[1] point_after
+                from (
+                    select self_clan_id clan_id, self_history_point history, self_now_point now, create_time from orange.track where round_id = $1
+                    union all
+                    select rival_clan_id, rival_history_point, rival_now_point, create_time from orange.track where round_id = $1
+                ) m, orange.clan c
+                where m.clan_id = c.id
+                group by m.clan_id, c.tag, c.name
+            ) p order by delta desc",
+        )
+        .bind(round_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn new(pool: &Pool<Postgres>, round: &Round) -> Result<Self, Error> {
+        let round_id = round.get_id();
+        let tracks = Track::select_round(pool, round_id).await?;
+        let rewards = OperateLog::select_round(pool, round_id).await?;
+        let point_moves = Self::point_moves(pool, round_id).await?;
+
+        let used: Vec<i64> = tracks
+            .iter()
+            .filter_map(|t| t.reward_info.as_ref())
+            .flat_map(|r| {
+                let (self_used, rival_used) = r.used();
+                [self_used, rival_used]
+            })
+            .collect();
+        let issued: Vec<i64> = rewards.iter().map(|r| r.amount).collect();
+
+        Ok(Self {
+            round_id,
+            code: round.get_code().to_string(),
+            tracks: tracks.len() as i64,
+            registered_clans: point_moves.len() as i64,
+            by_type: Self::by_type(pool, round_id).await?,
+            by_result: Self::by_result(pool, round_id).await?,
+            unregistered: Self::unregistered(pool, round).await?,
+            point_moves,
+            tickets_used: used.iter().filter(|u| **u > 0).sum(),
+            penalties_used: -used.iter().filter(|u| **u < 0).sum::<i64>(),
+            tickets_issued: issued.iter().filter(|i| **i > 0).sum(),
+            penalties_issued: -issued.iter().filter(|i| **i < 0).sum::<i64>(),
+            rewards,
+        })
+    }
+}
+
+impl RoundComparison {
+    fn new(summary: &RoundSummary, previous: &RoundSummary) -> Self {
+        Self {
+            previous_round_id: previous.round_id,
+            previous_code: previous.code.clone(),
+            tracks: summary.tracks - previous.tracks,
+            registered_clans: summary.registered_clans - previous.registered_clans,
+            unregistered: summary.unregistered.len() as i64 - previous.unregistered.len() as i64,
+            tickets_used: summary.tickets_used - previous.tickets_used,
+            tickets_issued: summary.tickets_issued - previous.tickets_issued,
+        }
+    }
+}
+
+impl RoundReport {
+    /// # 轮次报告
+    /// 附同服务器上一轮对比
+    pub async fn new(pool: &Pool<Postgres>, round: &Round) -> Result<Self, Error> {
+        let summary = RoundSummary::new(pool, round).await?;
+        let comparison = match Round::select_previous(pool, round).await {
+            Ok(previous) => {
+                let previous = RoundSummary::new(pool, &previous).await?;
+                Some(RoundComparison::new(&summary, &previous))
+            }
+            Err(Error::RowNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        log_info!(
+            "轮次报告 {} 对局 {} 未登记 {}",
+            summary.code,
+            summary.tracks,
+            summary.unregistered.len()
+        );
+        Ok(Self {
+            summary,
+            comparison,
+        })
+    }
+}
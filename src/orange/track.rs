@@ -1,17 +1,21 @@
 use crate::{
     api::MiddleTrackApi,
-    orange::{Clan, Round, clan_point::ClanPoint},
+    core::tag::ClanTag,
+    orange::{
+        Clan, ClanStatus, Round, clan_history::history_at, clan_point::ClanPoint,
+        dispute::DisputeStatus,
+    },
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use sqlx::{
-    Error, FromRow, Pool, Postgres, SqlStr, Type, postgres::PgQueryResult, query, query_as,
-    query_scalar, types::Json,
+    Error, FromRow, PgConnection, PgExecutor, Pool, Postgres, SqlStr, Type,
+    postgres::PgQueryResult, query, query_as, query_scalar, types::Json,
 };
 use std::fmt::{Display, Formatter};
 use uuid::Uuid;
-use void_log::{log_info, log_warn};
+use void_log::log_info;
 
 #[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
 pub struct Track {
@@ -28,10 +32,12 @@ pub struct Track {
     pub r#type: TrackType,
     pub reward_info: Option<Json<TrackRewardInfo>>,
     pub round_code: Option<String>,
-    pub self_tag: Option<String>,
+    pub self_tag: Option<ClanTag>,
     pub self_name: Option<String>,
-    pub rival_tag: Option<String>,
+    pub rival_tag: Option<ClanTag>,
     pub rival_name: Option<String>,
+    /// # 最近一次申诉状态
+    pub dispute_status: Option<DisputeStatus>,
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Type, Serialize_repr, Deserialize_repr)]
@@ -95,6 +101,16 @@ impl Display for Track {
     }
 }
 
+/// # 撤销的对局
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TrackRollback {
+    #[serde(flatten)]
+    pub track: Track,
+    /// # 退还的奖励券，负数为退还的处罚
+    pub self_reward_returned: i64,
+    pub rival_reward_returned: i64,
+}
+
 impl Display for TrackRewardInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -106,6 +122,14 @@ impl Display for TrackRewardInfo {
 }
 
 impl TrackRewardInfo {
+    /// # 本局消耗的奖励券
+    pub fn used(&self) -> (i64, i64) {
+        (
+            self.self_history - self.self_now,
+            self.rival_history - self.rival_now,
+        )
+    }
+
     fn new_history(self_history: i64, rival_history: i64) -> Self {
         Self {
             self_history,
@@ -121,14 +145,18 @@ impl TrackRewardInfo {
     }
 }
 
+/// # 对局查询
+/// 标签和名称取对局当时的历史值
 fn sql(sql_text: &str) -> &'static str {
-    let base_sql = "SELECT
+    let base_sql = format!(
+        "SELECT
             ot.*,
             r.code round_code,
-            c1.tag self_tag,
-            c1.NAME self_name,
-            c2.tag rival_tag,
-            c2.NAME rival_name
+            {} self_tag,
+            {} self_name,
+            {} rival_tag,
+            {} rival_name,
+            (select d.status from orange.track_dispute d where d.track_id = ot.id order by d.create_time desc limit 1) dispute_status
         FROM
             orange.track ot,
             orange.round r,
@@ -137,7 +165,12 @@ fn sql(sql_text: &str) -> &'static str {
         WHERE
             ot.round_id = r.\"id\"
             AND ot.self_clan_id = c1.\"id\"
-            AND ot.rival_clan_id = c2.\"id\"";
+            AND ot.rival_clan_id = c2.\"id\"",
+        history_at("c1", "ot.create_time", "tag"),
+        history_at("c1", "ot.create_time", "name"),
+        history_at("c2", "ot.create_time", "tag"),
+        history_at("c2", "ot.create_time", "name"),
+    );
     format!("{base_sql} {sql_text}").leak()
 }
 
@@ -148,18 +181,12 @@ impl Track {
 
     pub async fn new(
         pool: &Pool<Postgres>,
+        round: &Round,
         self_clan_point: Option<ClanPoint>,
         rival_clan_point: Option<ClanPoint>,
         self_tag: &Clan,
         is_global: bool,
     ) -> Option<Self> {
-        // 本场轮次
-        let round = Round::select_last(pool).await.unwrap_or_default();
-
-        if (round.get_create_time() + Duration::minutes(10)) > Utc::now() {
-            return None;
-        }
-
         // 初始化积分
         let scp = self_clan_point.unwrap_or_default();
         let rcp = rival_clan_point.unwrap_or_default();
@@ -185,7 +212,7 @@ impl Track {
             } else {
                 return None;
             };
-            return Some(ma.check_win(pool, track, is_global, self_tag).await);
+            return ma.check_win(pool, track, is_global, self_tag).await;
         }
         // ****************Track Failed 调用中间库****************
 
@@ -249,6 +276,50 @@ impl Track {
         Some(track)
     }
 
+    /// # 黑名单对局
+    /// 非黑名单一方必赢，双方积分和奖励券不变
+    pub async fn blacklist(
+        pool: &Pool<Postgres>,
+        first: &Clan,
+        last: &Clan,
+        round: &Round,
+    ) -> Option<Self> {
+        let is_black = |clan: &Clan| clan.status.is_some_and(|s| s == ClanStatus::Blacklist);
+        let (first_black, last_black) = (is_black(first), is_black(last));
+        if first_black == last_black {
+            return None;
+        }
+
+        let first_point = first
+            .point_select(pool)
+            .await
+            .map(|p| p.point)
+            .unwrap_or_default();
+        let last_point = last
+            .point_select(pool)
+            .await
+            .map(|p| p.point)
+            .unwrap_or_default();
+        let result = if last_black {
+            TrackResult::Win
+        } else {
+            TrackResult::Lose
+        };
+        Some(Self {
+            self_clan_id: first.get_id(),
+            rival_clan_id: last.get_id(),
+            self_history_point: first_point,
+            rival_history_point: last_point,
+            self_now_point: first_point,
+            rival_now_point: last_point,
+            create_time: Utc::now(),
+            round_id: round.get_id(),
+            result,
+            r#type: TrackType::Blacklist,
+            ..Default::default()
+        })
+    }
+
     /// # History Win Check
     async fn check_history(
         &mut self,
@@ -376,14 +447,37 @@ impl Track {
             .bind(clan_id).bind(round_id).fetch_all(pool).await
     }
 
-    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+    /// # 未统计的对局
+    /// 只取真实对战，不含奖惩局
+    pub async fn select_unstat(
+        pool: &Pool<Postgres>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Self>, Error> {
+        query_as(sql("and ot.type < 10 and ot.create_time > $1 and not exists (select 1 from orange.war_stat s where s.track_id = ot.id) order by ot.create_time"))
+            .bind(since)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 本局双方是否已有逆转局
+    pub async fn reversed(&self, executor: impl PgExecutor<'_>) -> Result<bool, Error> {
+        query_scalar("select count(id) > 0 from orange.track where type = $1 and round_id = $2 and self_clan_id = $3 and rival_clan_id = $4")
+            .bind(TrackType::Reverse)
+            .bind(self.round_id)
+            .bind(self.self_clan_id)
+            .bind(self.rival_clan_id)
+            .fetch_one(executor)
+            .await
+    }
+
+    pub async fn select(executor: impl PgExecutor<'_>, id: Uuid) -> Result<Self, Error> {
         query_as(sql("and ot.id = $1"))
             .bind(id)
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await
     }
 
-    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+    pub async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
         query("insert into orange.track values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)")
             .bind(&self.self_clan_id)
@@ -397,41 +491,93 @@ impl Track {
             .bind(&self.result)
             .bind(&self.r#type)
             .bind(&self.reward_info)
-            .execute(pool)
+            .execute(executor)
             .await
     }
 
     /// # 解除本场登记
+    /// 只能解除结算前轮次的记录
     pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
         log_info!("解除开始");
-        let round = Round::select_last(pool).await.unwrap_or_default();
         let track = Self::select(pool, id).await?;
-        log_info!(
-            "{} {}",
-            round.get_id().to_string(),
-            track.round_id.to_string()
-        );
-        if track.round_id.eq(&round.get_id()) {
-            log_info!("取消1: 是本场记录 {}", track.round_id == round.get_id());
-            let self_repair =
-                ClanPoint::repair_point(pool, track.self_clan_id, track.self_history_point)
-                    .await
-                    .is_err();
-            let rival_repair =
-                ClanPoint::repair_point(pool, track.rival_clan_id, track.rival_history_point)
-                    .await
-                    .is_err();
-            if self_repair || rival_repair {
-                log_warn!("取消外部匹配")
-            }
-            query("delete from orange.track where id = $1")
-                .bind(id)
-                .execute(pool)
-                .await
+        let round = Round::select(pool, track.round_id).await?;
+        log_info!("{} {:?}", round.get_id().to_string(), round.get_state());
+        if round.get_state().can_adjust() {
+            log_info!("取消1: 轮次可调整 {}", round.get_code());
+            let mut tx = pool.begin().await?;
+            // 外部部落无积分记录，不影响
+            ClanPoint::repair_point(&mut *tx, track.self_clan_id, track.self_history_point).await?;
+            ClanPoint::repair_point(&mut *tx, track.rival_clan_id, track.rival_history_point)
+                .await?;
+            let res = Self::delete_cascade(&mut tx, id).await?;
+            tx.commit().await?;
+            Ok(res)
         } else {
             Err(Error::RowNotFound)
         }
     }
+
+    /// # 删除对局及其统计
+    async fn delete_cascade(conn: &mut PgConnection, id: Uuid) -> Result<PgQueryResult, Error> {
+        for sql in [
+            "delete from orange.war_attack where track_id = $1",
+            "delete from orange.war_stat where track_id = $1",
+            "delete from orange.track_dispute where track_id = $1",
+            "delete from orange.track_correct where track_id = $1",
+        ] {
+            query(sql).bind(id).execute(&mut *conn).await?;
+        }
+        query("delete from orange.track where id = $1")
+            .bind(id)
+            .execute(conn)
+            .await
+    }
+
+    /// # 轮次全部对局
+    /// 新的在前
+    pub async fn select_round(
+        executor: impl PgExecutor<'_>,
+        round_id: Uuid,
+    ) -> Result<Vec<Self>, Error> {
+        query_as(sql("and ot.round_id = $1 order by ot.create_time desc"))
+            .bind(round_id)
+            .fetch_all(executor)
+            .await
+    }
+
+    /// # 撤销对局
+    /// 恢复双方登记前积分，退还本局使用的奖励券，删除对局及其统计
+    pub async fn rollback(self, conn: &mut PgConnection) -> Result<TrackRollback, Error> {
+        ClanPoint::repair_point(&mut *conn, self.self_clan_id, self.self_history_point).await?;
+        ClanPoint::repair_point(&mut *conn, self.rival_clan_id, self.rival_history_point).await?;
+
+        let (self_used, rival_used) = self
+            .reward_info
+            .as_ref()
+            .map(|r| r.used())
+            .unwrap_or_default();
+        for (clan_id, used) in [
+            (self.self_clan_id, self_used),
+            (self.rival_clan_id, rival_used),
+        ] {
+            if used == 0 {
+                continue;
+            }
+            if let Ok(clan_point) = ClanPoint::select(&mut *conn, clan_id).await {
+                clan_point
+                    .update_reward_point_base(&mut *conn, used)
+                    .await?;
+            }
+        }
+
+        Self::delete_cascade(conn, self.id).await?;
+        log_info!("撤销对局 {}", &self);
+        Ok(TrackRollback {
+            track: self,
+            self_reward_returned: self_used,
+            rival_reward_returned: rival_used,
+        })
+    }
 }
 
 impl Round {
@@ -0,0 +1,294 @@
+use crate::orange::{ClanPoint, Round, Track, TrackResult, TrackType, round::RoundState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgConnection, Pool, Postgres, query, query_as, types::Json};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::log_info;
+
+/// # 对局更正记录
+/// 保留更正前后两个版本
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct TrackCorrect {
+    pub id: Uuid,
+    pub track_id: Uuid,
+    pub original: Json<Track>,
+    pub corrected: Json<Track>,
+    pub reason: String,
+    /// # 先手方积分调整
+    pub self_delta: i64,
+    /// # 后手方积分调整
+    pub rival_delta: i64,
+    /// # 顺延调整的后续对局数
+    pub shifted: i64,
+    pub correct_by: Uuid,
+    pub create_time: DateTime<Utc>,
+}
+
+/// # 更正内容
+/// 结果和类型至少改一项，原因必填
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TrackCorrectNew {
+    pub result: Option<TrackResult>,
+    pub r#type: Option<TrackType>,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub enum CorrectError {
+    EmptyReason,
+    Unchanged,
+    /// # 奖惩局涉及奖励券，不能更正
+    RewardTrack,
+    /// # 已取消轮次的对局
+    Cancelled,
+    /// # 已逆转的对局，积分已由逆转调整
+    Reversed,
+    /// # 不能更正为逆转局，逆转走逆转流程
+    ReverseType,
+    Database(Error),
+}
+
+impl Display for CorrectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg_en())
+    }
+}
+
+impl From<Error> for CorrectError {
+    fn from(value: Error) -> Self {
+        CorrectError::Database(value)
+    }
+}
+
+impl CorrectError {
+    pub fn msg_en(&self) -> String {
+        match self {
+            CorrectError::EmptyReason => "Reason is required".to_string(),
+            CorrectError::Unchanged => "Nothing to correct".to_string(),
+            CorrectError::RewardTrack => {
+                "Award and penalty tracks can not be corrected".to_string()
+            }
+            CorrectError::Cancelled => "Round is cancelled".to_string(),
+            CorrectError::Reversed => "Reversed tracks can not be corrected".to_string(),
+            CorrectError::ReverseType => "Use reverse to reverse a track".to_string(),
+            CorrectError::Database(e) => format!("Database error: {e}"),
+        }
+    }
+
+    pub fn msg_cn(&self) -> String {
+        match self {
+            CorrectError::EmptyReason => "请填写更正原因".to_string(),
+            CorrectError::Unchanged => "结果和类型均未改变".to_string(),
+            CorrectError::RewardTrack => "奖惩局不能更正".to_string(),
+            CorrectError::Cancelled => "轮次已取消".to_string(),
+            CorrectError::Reversed => "已逆转的对局不能更正".to_string(),
+            CorrectError::ReverseType => "逆转请使用逆转功能".to_string(),
+            CorrectError::Database(e) => format!("数据库错误: {e}"),
+        }
+    }
+}
+
+impl TrackType {
+    /// # 奖惩局
+    pub fn is_reward(&self) -> bool {
+        matches!(self, TrackType::Award | TrackType::Penalty)
+    }
+
+    /// # 双方积分变化
+    /// 只有内部和逆转局计分
+    pub fn point_delta(&self, result: &TrackResult) -> (i64, i64) {
+        match (self, result) {
+            (TrackType::Internal | TrackType::Reverse, TrackResult::Win) => (1, -1),
+            (TrackType::Internal | TrackType::Reverse, TrackResult::Lose) => (-1, 1),
+            _ => (0, 0),
+        }
+    }
+}
+
+impl Track {
+    /// # 按更正内容重算
+    /// 返回更正后的对局和双方积分调整
+    fn corrected(&self, correct: &TrackCorrectNew) -> (Self, i64, i64) {
+        let mut corrected = self.clone();
+        corrected.result = correct.result.clone().unwrap_or(self.result.clone());
+        corrected.r#type = correct.r#type.clone().unwrap_or(self.r#type.clone());
+        let (self_delta, rival_delta) = corrected.r#type.point_delta(&corrected.result);
+        corrected.self_now_point = corrected.self_history_point + self_delta;
+        corrected.rival_now_point = corrected.rival_history_point + rival_delta;
+        (
+            corrected.clone(),
+            corrected.self_now_point - self.self_now_point,
+            corrected.rival_now_point - self.rival_now_point,
+        )
+    }
+
+    async fn update_correct(&self, conn: &mut PgConnection) -> Result<(), Error> {
+        query("update orange.track set result = $1, type = $2, self_now_point = $3, rival_now_point = $4 where id = $5")
+            .bind(&self.result)
+            .bind(&self.r#type)
+            .bind(self.self_now_point)
+            .bind(self.rival_now_point)
+            .bind(self.id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// # 顺延后续对局积分，并调整部落当前积分
+    async fn shift_after(
+        &self,
+        conn: &mut PgConnection,
+        clan_id: Uuid,
+        delta: i64,
+    ) -> Result<u64, Error> {
+        if delta == 0 || clan_id == Uuid::default() {
+            return Ok(0);
+        }
+        let self_side = query("update orange.track set self_history_point = self_history_point + $1, self_now_point = self_now_point + $1 where self_clan_id = $2 and create_time > $3")
+            .bind(delta)
+            .bind(clan_id)
+            .bind(self.create_time)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        let rival_side = query("update orange.track set rival_history_point = rival_history_point + $1, rival_now_point = rival_now_point + $1 where rival_clan_id = $2 and create_time > $3")
+            .bind(delta)
+            .bind(clan_id)
+            .bind(self.create_time)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        if let Ok(clan_point) = ClanPoint::select(&mut *conn, clan_id).await {
+            clan_point.update_point(&mut *conn, delta).await?;
+        }
+        Ok(self_side + rival_side)
+    }
+}
+
+impl TrackCorrect {
+    /// # 对局更正历史
+    pub async fn select_track(pool: &Pool<Postgres>, track_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select * from orange.track_correct where track_id = $1 order by create_time desc")
+            .bind(track_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn insert(&self, conn: &mut PgConnection) -> Result<(), Error> {
+        query(
+            "insert into orange.track_correct values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(self.track_id)
+        .bind(&self.original)
+        .bind(&self.corrected)
+        .bind(&self.reason)
+        .bind(self.self_delta)
+        .bind(self.rival_delta)
+        .bind(self.shifted)
+        .bind(self.correct_by)
+        .bind(self.create_time)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// # 更正对局
+    /// 重算双方积分，后续对局的历史积分和当前积分一并顺延
+    /// 已逆转的对局不能更正，也不能改为逆转局
+    /// 更正、顺延和记录同一事务，失败不留部分调整
+    pub async fn correct(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        correct: &TrackCorrectNew,
+        correct_by: Uuid,
+    ) -> Result<Self, CorrectError> {
+        if correct.reason.trim().is_empty() {
+            return Err(CorrectError::EmptyReason);
+        }
+        let original = Track::select(pool, id).await?;
+        if Round::select(pool, original.round_id).await?.get_state() == RoundState::Cancelled {
+            return Err(CorrectError::Cancelled);
+        }
+        let (corrected, self_delta, rival_delta) = original.corrected(correct);
+        if corrected.result == original.result && corrected.r#type == original.r#type {
+            return Err(CorrectError::Unchanged);
+        }
+        if original.r#type.is_reward() || corrected.r#type.is_reward() {
+            return Err(CorrectError::RewardTrack);
+        }
+        if original.reversed(pool).await? {
+            return Err(CorrectError::Reversed);
+        }
+        if corrected.r#type == TrackType::Reverse {
+            return Err(CorrectError::ReverseType);
+        }
+
+        let mut tx = pool.begin().await?;
+        corrected.update_correct(&mut tx).await?;
+        let shifted = original
+            .shift_after(&mut tx, original.self_clan_id, self_delta)
+            .await?
+            + original
+                .shift_after(&mut tx, original.rival_clan_id, rival_delta)
+                .await?;
+
+        let track_correct = Self {
+            track_id: id,
+            original: Json(original),
+            corrected: Json(corrected),
+            reason: correct.reason.trim().to_string(),
+            self_delta,
+            rival_delta,
+            shifted: shifted as i64,
+            correct_by,
+            create_time: Utc::now(),
+            ..Default::default()
+        };
+        track_correct.insert(&mut tx).await?;
+        tx.commit().await?;
+        log_info!(
+            "更正对局 {} 积分调整 {} / {} 顺延 {}",
+            id,
+            self_delta,
+            rival_delta,
+            shifted
+        );
+        Ok(track_correct)
+    }
+}
+
+#[test]
+fn test_corrected() {
+    let track = Track {
+        self_history_point: 10,
+        rival_history_point: 12,
+        self_now_point: 11,
+        rival_now_point: 11,
+        result: TrackResult::Win,
+        r#type: TrackType::Internal,
+        ..Default::default()
+    };
+
+    // 改判为负
+    let (corrected, self_delta, rival_delta) = track.corrected(&TrackCorrectNew {
+        result: Some(TrackResult::Lose),
+        ..Default::default()
+    });
+    assert_eq!(
+        (corrected.self_now_point, corrected.rival_now_point),
+        (9, 13)
+    );
+    assert_eq!((self_delta, rival_delta), (-2, 2));
+
+    // 改为黑名单局，积分恢复登记前
+    let (corrected, self_delta, rival_delta) = track.corrected(&TrackCorrectNew {
+        r#type: Some(TrackType::Blacklist),
+        ..Default::default()
+    });
+    assert_eq!(
+        (corrected.self_now_point, corrected.rival_now_point),
+        (10, 12)
+    );
+    assert_eq!((self_delta, rival_delta), (-1, 1));
+}
@@ -0,0 +1,371 @@
+use crate::{
+    api::{self, War, WarClan},
+    core::tag::{ClanTag, PlayerTag},
+    orange::{Track, TrackResult},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, PgConnection, PgExecutor, Pool, Postgres, postgres::PgQueryResult, query,
+    query_as,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 对战结果统计
+/// 每场登记的对局双方各一条
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct WarStat {
+    pub id: Uuid,
+    pub track_id: Uuid,
+    pub round_id: Uuid,
+    pub clan_id: Uuid,
+    pub stars: i64,
+    pub destruction: f64,
+    pub attacks: i64,
+    pub attacks_available: i64,
+    /// # 登记时的指定结果
+    pub told_result: TrackResult,
+    /// # 实际结果
+    pub actual_result: TrackResult,
+    pub end_time: Option<DateTime<Utc>>,
+    pub create_time: DateTime<Utc>,
+    #[sqlx(default)]
+    pub tag: Option<String>,
+    #[sqlx(default)]
+    pub name: Option<String>,
+}
+
+/// # 成员进攻记录
+/// 未进攻的成员也记录一条，进攻字段为空
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct WarAttack {
+    pub id: Uuid,
+    pub track_id: Uuid,
+    pub clan_id: Uuid,
+    pub player_tag: PlayerTag,
+    pub player_name: Option<String>,
+    pub town_hall_level: Option<i64>,
+    pub map_position: Option<i64>,
+    pub attack_order: Option<i64>,
+    pub defender_tag: Option<PlayerTag>,
+    pub stars: Option<i64>,
+    pub destruction: Option<f64>,
+    pub duration: Option<i64>,
+    #[sqlx(default)]
+    pub round_code: Option<String>,
+}
+
+/// # 单场详情
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WarDetail {
+    pub stats: Vec<WarStat>,
+    pub attacks: Vec<WarAttack>,
+}
+
+/// # 执行情况
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WarCompliance {
+    #[serde(flatten)]
+    pub stat: WarStat,
+    /// # 实际结果与指定结果一致
+    pub compliant: bool,
+    pub unused_attacks: i64,
+}
+
+impl Display for WarStat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "War Stat {} {:?}\n - Stars: {} | Destruction: {:.2} | Attacks: {}/{}\n - Told: {:?} | Actual: {:?}",
+            self.track_id,
+            self.tag,
+            self.stars,
+            self.destruction,
+            self.attacks,
+            self.attacks_available,
+            self.told_result,
+            self.actual_result
+        )
+    }
+}
+
+impl TrackResult {
+    /// # 对方视角
+    pub fn opposite(&self) -> Self {
+        match self {
+            TrackResult::Win => TrackResult::Lose,
+            TrackResult::None => TrackResult::None,
+            TrackResult::Lose => TrackResult::Win,
+        }
+    }
+}
+
+/// # 实际胜负
+/// 先比星数再比摧毁率
+fn actual_result(clan: &WarClan, opponent: &WarClan) -> TrackResult {
+    let stars = clan.stars.unwrap_or_default();
+    let opponent_stars = opponent.stars.unwrap_or_default();
+    let destruction = clan.destruction_percentage.unwrap_or_default();
+    let opponent_destruction = opponent.destruction_percentage.unwrap_or_default();
+    if stars != opponent_stars {
+        if stars > opponent_stars {
+            TrackResult::Win
+        } else {
+            TrackResult::Lose
+        }
+    } else if destruction > opponent_destruction {
+        TrackResult::Win
+    } else if destruction < opponent_destruction {
+        TrackResult::Lose
+    } else {
+        TrackResult::None
+    }
+}
+
+impl WarStat {
+    /// # 统计范围
+    /// 对局登记后超过该时间不再抓取
+    const CAPTURE_WINDOW: Duration = Duration::days(3);
+
+    fn new(track: &Track, clan_id: Uuid, war: &War, clan: &WarClan, opponent: &WarClan) -> Self {
+        let told_result = if clan_id == track.self_clan_id {
+            track.result.clone()
+        } else {
+            track.result.opposite()
+        };
+        Self {
+            track_id: track.id,
+            round_id: track.round_id,
+            clan_id,
+            stars: clan.stars.unwrap_or_default(),
+            destruction: clan.destruction_percentage.unwrap_or_default(),
+            attacks: clan.attacks.unwrap_or_default(),
+            attacks_available: war.attacks_available(),
+            told_result,
+            actual_result: actual_result(clan, opponent),
+            end_time: war.end_time_utc(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.told_result == TrackResult::None || self.told_result == self.actual_result
+    }
+
+    /// # 单场统计
+    pub async fn select_track(pool: &Pool<Postgres>, track_id: Uuid) -> Result<WarDetail, Error> {
+        let stats = query_as("select s.*, c.tag, c.name from orange.war_stat s, orange.clan c where s.clan_id = c.id and s.track_id = $1")
+            .bind(track_id)
+            .fetch_all(pool)
+            .await?;
+        let attacks = query_as(
+            "select * from orange.war_attack where track_id = $1 order by clan_id, map_position",
+        )
+        .bind(track_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(WarDetail { stats, attacks })
+    }
+
+    /// # 轮次执行情况
+    pub async fn compliance(
+        pool: &Pool<Postgres>,
+        round_id: Uuid,
+    ) -> Result<Vec<WarCompliance>, Error> {
+        let stats: Vec<Self> = query_as("select s.*, c.tag, c.name from orange.war_stat s, orange.clan c where s.clan_id = c.id and s.round_id = $1 order by s.track_id")
+            .bind(round_id)
+            .fetch_all(pool)
+            .await?;
+        Ok(stats
+            .into_iter()
+            .map(|stat| WarCompliance {
+                compliant: stat.is_compliant(),
+                unused_attacks: (stat.attacks_available - stat.attacks).max(0),
+                stat,
+            })
+            .collect())
+    }
+
+    async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.war_stat values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) on conflict (track_id, clan_id) do nothing")
+            .bind(self.track_id)
+            .bind(self.round_id)
+            .bind(self.clan_id)
+            .bind(self.stars)
+            .bind(self.destruction)
+            .bind(self.attacks)
+            .bind(self.attacks_available)
+            .bind(&self.told_result)
+            .bind(&self.actual_result)
+            .bind(self.end_time)
+            .bind(Utc::now())
+            .execute(executor)
+            .await
+    }
+
+    /// # 写入单场统计
+    /// 双方统计和进攻记录同一事务写入，失败不留半场数据，下次重新抓取
+    async fn insert_war(
+        pool: &Pool<Postgres>,
+        track: &Track,
+        war: &War,
+        clan: &WarClan,
+        opponent: &WarClan,
+    ) -> Result<(), Error> {
+        let mut tx = pool.begin().await?;
+        Self::new(track, track.self_clan_id, war, clan, opponent)
+            .insert(&mut *tx)
+            .await?;
+        Self::new(track, track.rival_clan_id, war, opponent, clan)
+            .insert(&mut *tx)
+            .await?;
+        WarAttack::insert_clan(&mut tx, track.id, track.self_clan_id, clan).await?;
+        WarAttack::insert_clan(&mut tx, track.id, track.rival_clan_id, opponent).await?;
+        tx.commit().await
+    }
+
+    /// # 抓取已结束的对战
+    /// 从先手方当前对战读取，对手与登记不符时跳过
+    pub async fn capture_all(pool: &Pool<Postgres>) -> Result<usize, Error> {
+        let since = Utc::now() - Self::CAPTURE_WINDOW;
+        let mut count = 0;
+        for track in Track::select_unstat(pool, since).await? {
+            let Some(self_tag) = &track.self_tag else {
+                continue;
+            };
+            let war = War::get(self_tag).await;
+            tokio::time::sleep(api::API_INTERVAL).await;
+            if !war.is_ended() {
+                continue;
+            }
+            let (Some(clan), Some(opponent)) = (&war.clan, &war.opponent) else {
+                continue;
+            };
+            let opponent_tag = opponent.tag.as_deref().and_then(|t| ClanTag::new(t).ok());
+            if opponent_tag != track.rival_tag {
+                log_warn!(
+                    "对战统计跳过 {} 对手 {:?} 登记 {:?}",
+                    self_tag,
+                    opponent_tag,
+                    track.rival_tag
+                );
+                continue;
+            }
+
+            // 单场失败不影响其他对局
+            if let Err(e) = Self::insert_war(pool, &track, &war, clan, opponent).await {
+                log_warn!("对战统计失败 {} {e}", track.id);
+                continue;
+            }
+            count += 1;
+        }
+        log_info!("对战统计完成 {count}");
+        Ok(count)
+    }
+}
+
+impl WarAttack {
+    /// # 玩家历史进攻
+    pub async fn select_player(
+        pool: &Pool<Postgres>,
+        player_tag: &PlayerTag,
+    ) -> Result<Vec<Self>, Error> {
+        query_as("select a.*, r.code round_code from orange.war_attack a, orange.track t, orange.round r where a.track_id = t.id and t.round_id = r.id and a.player_tag = $1 order by r.create_time desc, a.attack_order")
+            .bind(player_tag)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn insert(&self, executor: impl PgExecutor<'_>) -> Result<PgQueryResult, Error> {
+        query("insert into orange.war_attack values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)")
+            .bind(self.track_id)
+            .bind(self.clan_id)
+            .bind(&self.player_tag)
+            .bind(&self.player_name)
+            .bind(self.town_hall_level)
+            .bind(self.map_position)
+            .bind(self.attack_order)
+            .bind(&self.defender_tag)
+            .bind(self.stars)
+            .bind(self.destruction)
+            .bind(self.duration)
+            .execute(executor)
+            .await
+    }
+
+    /// # 写入一方全部成员
+    async fn insert_clan(
+        conn: &mut PgConnection,
+        track_id: Uuid,
+        clan_id: Uuid,
+        war_clan: &WarClan,
+    ) -> Result<(), Error> {
+        for member in war_clan.members.iter().flatten() {
+            let Some(player_tag) = member.tag.as_deref().and_then(|t| PlayerTag::new(t).ok())
+            else {
+                continue;
+            };
+            let base = Self {
+                track_id,
+                clan_id,
+                player_tag,
+                player_name: member.name.clone(),
+                town_hall_level: member.town_hall_level,
+                map_position: member.map_position,
+                id: Uuid::default(),
+                attack_order: None,
+                defender_tag: None,
+                stars: None,
+                destruction: None,
+                duration: None,
+                round_code: None,
+            };
+            let attacks = member.attacks.as_deref().unwrap_or_default();
+            if attacks.is_empty() {
+                base.insert(&mut *conn).await?;
+            }
+            for attack in attacks {
+                let record = Self {
+                    attack_order: attack.order,
+                    defender_tag: attack
+                        .defender_tag
+                        .as_deref()
+                        .and_then(|t| PlayerTag::new(t).ok()),
+                    stars: attack.stars,
+                    destruction: attack.destruction_percentage,
+                    duration: attack.duration,
+                    ..base.clone()
+                };
+                record.insert(&mut *conn).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_actual_result() {
+    let clan = |stars, destruction| WarClan {
+        stars: Some(stars),
+        destruction_percentage: Some(destruction),
+        ..Default::default()
+    };
+    assert_eq!(
+        actual_result(&clan(30, 90.0), &clan(28, 99.0)),
+        TrackResult::Win
+    );
+    assert_eq!(
+        actual_result(&clan(28, 99.0), &clan(30, 90.0)),
+        TrackResult::Lose
+    );
+    assert_eq!(
+        actual_result(&clan(30, 95.5), &clan(30, 95.0)),
+        TrackResult::Win
+    );
+    assert_eq!(
+        actual_result(&clan(30, 95.0), &clan(30, 95.0)),
+        TrackResult::None
+    );
+}
@@ -0,0 +1,242 @@
+use crate::safety::authorization::VoidToken;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, query_scalar,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::log_warn;
+
+/// # 接口密钥
+/// 明文格式: `{id}.{secret}`，数据库只存secret的Hash
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct ApiKey {
+    #[serde(skip_deserializing)]
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(skip)]
+    key_hash: String,
+    pub expire_time: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    create_time: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    create_by: Option<Uuid>,
+    #[serde(skip_deserializing)]
+    last_used_time: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    revoke_time: Option<DateTime<Utc>>,
+}
+
+/// # 签发/轮换返回
+/// `key` 只返回这一次
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ApiKeySecret {
+    pub id: Uuid,
+    pub key: String,
+}
+
+/// # 接口权限范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiScope {
+    /// # 登录
+    LoginAuth,
+    /// # 部落查询
+    ClanSelect,
+    /// # 中间库查询
+    MiddleTrackSelect,
+    /// # 操作日志查询
+    OperateLogSelect,
+}
+
+impl ApiScope {
+    /// # 全部权限
+    pub const ALL: &'static str = "*";
+
+    const SCOPES: [ApiScope; 4] = [
+        ApiScope::LoginAuth,
+        ApiScope::ClanSelect,
+        ApiScope::MiddleTrackSelect,
+        ApiScope::OperateLogSelect,
+    ];
+
+    /// # 是否已知权限编码
+    pub fn is_valid(code: &str) -> bool {
+        code == Self::ALL || Self::SCOPES.iter().any(|s| s.code() == code)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiScope::LoginAuth => "login*auth",
+            ApiScope::ClanSelect => "clan*select",
+            ApiScope::MiddleTrackSelect => "middle*track*select",
+            ApiScope::OperateLogSelect => "operate*log*select",
+        }
+    }
+}
+
+impl Display for ApiKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ApiKey {}\n - Name: {}\n - Scopes: {:?}\n - Expire: {:?}\n - LastUsed: {:?}\n - Revoke: {:?}",
+            self.id,
+            self.name,
+            self.scopes,
+            self.expire_time,
+            self.last_used_time,
+            self.revoke_time
+        )
+    }
+}
+
+impl ApiKey {
+    /// # 未知权限编码
+    pub fn unknown_scopes(&self) -> Vec<&str> {
+        self.scopes
+            .iter()
+            .map(String::as_str)
+            .filter(|s| !ApiScope::is_valid(s))
+            .collect()
+    }
+
+    fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes
+            .iter()
+            .any(|s| s == ApiScope::ALL || s == scope.code())
+    }
+
+    fn is_active(&self) -> bool {
+        self.revoke_time.is_none() && self.expire_time.is_none_or(|t| t > Utc::now())
+    }
+
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from public.api_key order by create_time desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from public.api_key where id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// # 签发密钥
+    pub async fn insert(
+        &self,
+        pool: &Pool<Postgres>,
+        create_by: Uuid,
+    ) -> Result<ApiKeySecret, Error> {
+        let secret = VoidToken::new_token();
+        let id: Uuid = query_scalar(
+            "insert into public.api_key values(DEFAULT, $1, $2, $3, $4, $5, $6) returning id",
+        )
+        .bind(&self.name)
+        .bind(&self.scopes)
+        .bind(secret.hash())
+        .bind(self.expire_time)
+        .bind(Utc::now())
+        .bind(create_by)
+        .fetch_one(pool)
+        .await?;
+        Ok(ApiKeySecret::new(id, &secret))
+    }
+
+    /// # 轮换密钥
+    /// 旧密钥立即失效
+    pub async fn rotate(pool: &Pool<Postgres>, id: Uuid) -> Result<ApiKeySecret, Error> {
+        let secret = VoidToken::new_token();
+        let res =
+            query("update public.api_key set key_hash = $1 where id = $2 and revoke_time is null")
+                .bind(secret.hash())
+                .bind(id)
+                .execute(pool)
+                .await?;
+        if res.rows_affected() == 0 {
+            return Err(Error::RowNotFound);
+        }
+        Ok(ApiKeySecret::new(id, &secret))
+    }
+
+    /// # 吊销密钥
+    pub async fn revoke(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        query("update public.api_key set revoke_time = $1 where id = $2 and revoke_time is null")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+
+    async fn update_last_used(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("update public.api_key set last_used_time = $1 where id = $2")
+            .bind(Utc::now())
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 密钥编号
+    /// 只取 `.` 前的编号部分，用于日志
+    pub fn key_id(token: &str) -> &str {
+        token.split_once('.').map(|(id, _)| id).unwrap_or("-")
+    }
+
+    /// # 接口鉴权
+    /// 校验密钥、有效期和权限范围，通过后记录使用时间
+    pub async fn check(pool: &Pool<Postgres>, token: &str, scope: ApiScope) -> bool {
+        let (id, secret) = if let Some((id, secret)) = token.split_once('.') {
+            (id, VoidToken::new(secret))
+        } else {
+            log_warn!("ApiKey format error");
+            return false;
+        };
+
+        let api_key = if let Ok(id) = Uuid::parse_str(id)
+            && let Ok(api_key) = Self::select(pool, id).await
+        {
+            api_key
+        } else {
+            log_warn!("ApiKey not found {id}");
+            return false;
+        };
+
+        if !api_key.is_active() || !api_key.has_scope(scope) {
+            log_warn!("ApiKey refused {}", &api_key);
+            return false;
+        }
+
+        // Hash校验耗时，放到阻塞线程
+        let key_hash = api_key.key_hash.clone();
+        let verified = tokio::task::spawn_blocking(move || secret.verify(&key_hash))
+            .await
+            .unwrap_or(false);
+        if !verified {
+            log_warn!("ApiKey hash failed {}", api_key.id);
+            return false;
+        }
+
+        if let Err(e) = api_key.update_last_used(pool).await {
+            log_warn!("ApiKey last used {e}");
+        }
+        true
+    }
+}
+
+impl ApiKeySecret {
+    fn new(id: Uuid, secret: &VoidToken) -> Self {
+        Self {
+            id,
+            key: format!("{}.{}", id.simple(), secret.as_str()),
+        }
+    }
+}
+
+#[test]
+fn test_scope_valid() {
+    assert!(ApiScope::is_valid(ApiScope::ALL));
+    assert!(ApiScope::is_valid("clan*select"));
+    assert!(!ApiScope::is_valid("clan_select"));
+}
@@ -1,31 +1,51 @@
 use argon2::{
-    password_hash::PasswordHasher,
     Argon2,
+    password_hash::{PasswordHasher, PasswordVerifier, phc::PasswordHash},
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// # 密钥明文
+/// 仅在签发时返回一次，数据库只存Hash
 #[derive(Serialize, Deserialize, Debug)]
 pub struct VoidToken(String);
 
 impl VoidToken {
     pub fn new_token() -> Self {
-        let uuid = Uuid::new_v4().to_string();
+        Self(Uuid::new_v4().simple().to_string())
+    }
 
-        let argon2 = Argon2::default();
+    pub fn new(token: &str) -> Self {
+        Self(token.to_string())
+    }
 
-        // Hash password to PHC string ($argon2id$v=19$...)
-        let hash = argon2
-            .hash_password(uuid.as_bytes())
-            .unwrap()
-            .to_string();
-        Self(hash)
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
-}
 
+    /// # 密钥Hash
+    /// Hash password to PHC string ($argon2id$v=19$...)
+    pub fn hash(&self) -> String {
+        let argon2 = Argon2::default();
+        argon2.hash_password(self.0.as_bytes()).unwrap().to_string()
+    }
 
+    /// # 校验密钥
+    pub fn verify(&self, hash: &str) -> bool {
+        if let Ok(parsed_hash) = PasswordHash::new(hash) {
+            Argon2::default()
+                .verify_password(self.0.as_bytes(), &parsed_hash)
+                .is_ok()
+        } else {
+            false
+        }
+    }
+}
 
 #[test]
 fn test() {
-    println!("{:?}", VoidToken::new_token());
+    let token = VoidToken::new_token();
+    let hash = token.hash();
+    println!("{:?} {}", token, hash);
+    assert!(token.verify(&hash));
 }
@@ -1,18 +1,23 @@
-use crate::{AppState, safety::authorization::VoidToken, system::LoginLog, util::RestApi};
+use crate::{
+    AppState,
+    system::{AuditLog, LoginLog, UserInfo},
+    util::RestApi,
+};
 use axum::{
-    Router,
+    Json, Router,
     extract::{Path, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    routing::get,
+    routing::{get, put},
 };
-use axum_msgpack::MsgPack;
-use void_log::log_info;
+use axum_auth::AuthBearer;
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
 
+mod api_key;
 mod authorization;
 
-async fn get_token() -> impl IntoResponse {
-    MsgPack(VoidToken::new_token())
-}
+pub use api_key::{ApiKey, ApiScope};
 
 async fn login_log(State(app_state): State<AppState>) -> impl IntoResponse {
     let data = LoginLog::select_all(&app_state.pool)
@@ -45,10 +50,146 @@ async fn get_login_log(
     RestApi::new_successful(data).builder_msgpack()
 }
 
+async fn api_keys(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ApiKey::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 签发密钥
+/// 明文只在本次返回
+async fn api_key_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<ApiKey>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    if data.name.is_empty() || data.scopes.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Name and scopes required", "名称和权限不能为空"),
+        );
+    }
+    let unknown = data.unknown_scopes();
+    if !unknown.is_empty() {
+        log_warn!("ApiKey unknown scopes {unknown:?}");
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed(
+                format!("Unknown scopes: {}", unknown.join(", ")),
+                format!("未知权限: {}", unknown.join(", ")),
+            ),
+        );
+    }
+
+    let res = data.insert(&app_state.pool, user.get_id()).await;
+    if let Ok(r) = res {
+        log_info!("ApiKey issued {} by {}", r.id, user.get_id());
+        AuditLog::new(&user, "insert", "api_key", &header_map)
+            .entity_id(r.id)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Insert Failed", "添加失败"),
+        )
+    }
+}
+
+/// # 轮换密钥
+async fn api_key_rotate(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = ApiKey::rotate(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user, "rotate", "api_key", &header_map)
+            .entity_id(id)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (
+            StatusCode::GONE,
+            RestApi::failed("ApiKey not found or revoked", "密钥不存在或已吊销"),
+        )
+    }
+}
+
+/// # 吊销密钥
+async fn api_key_revoke(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = ApiKey::select(&app_state.pool, id).await.ok();
+    let res = ApiKey::revoke(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user, "revoke", "api_key", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .after(&ApiKey::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (StatusCode::UNPROCESSABLE_ENTITY, RestApi::error())
+    }
+}
+
 pub fn router() -> Router<AppState> {
     Router::new()
-        .route("/get_token", get(get_token))
         .route("/login_log", get(login_log))
         .route("/login_log_{page}/{page_size}", get(login_log_page))
         .route("/login_log/{text}", get(get_login_log))
+        // 接口密钥相关
+        .route("/api_key", get(api_keys).post(api_key_insert))
+        .route("/api_key/{id}", put(api_key_rotate).delete(api_key_revoke))
 }
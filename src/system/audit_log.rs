@@ -0,0 +1,194 @@
+use crate::{system::UserInfo, util::real_ip};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sqlx::{
+    Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as, query_scalar,
+    types::Json,
+};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+use void_log::{log_info, log_warn};
+
+/// # 审计日志
+/// 记录所有写操作的操作人、对象和前后差异
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub actor_id: Uuid,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: Option<Uuid>,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+    pub diff: Option<Json<Value>>,
+    pub address: String,
+    pub create_time: DateTime<Utc>,
+    #[sqlx(default)]
+    pub code: Option<String>,
+    #[sqlx(default)]
+    pub name: Option<String>,
+}
+
+/// # 审计查询条件
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub actor_id: Option<Uuid>,
+    pub entity: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+impl Display for AuditLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Audit Log\n - Actor: {}\n - Action: {} {} {:?}\n - Address: {}",
+            self.actor_id, self.action, self.entity, self.entity_id, self.address
+        )
+    }
+}
+
+impl AuditLog {
+    pub fn new(user_info: &UserInfo, action: &str, entity: &str, header_map: &HeaderMap) -> Self {
+        Self {
+            actor_id: user_info.get_id(),
+            action: action.to_string(),
+            entity: entity.to_string(),
+            address: real_ip(header_map),
+            create_time: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    pub fn entity_id(mut self, entity_id: Uuid) -> Self {
+        self.entity_id = Some(entity_id);
+        self
+    }
+
+    pub fn before<T: Serialize>(mut self, data: &T) -> Self {
+        self.before = serde_json::to_value(data).ok().map(Json);
+        self
+    }
+
+    pub fn after<T: Serialize>(mut self, data: &T) -> Self {
+        self.after = serde_json::to_value(data).ok().map(Json);
+        self
+    }
+
+    pub async fn select_page(
+        pool: &Pool<Postgres>,
+        audit_query: &AuditQuery,
+    ) -> Result<Vec<Self>, Error> {
+        let page = audit_query.page.unwrap_or(1);
+        let page_size = audit_query.page_size.unwrap_or(20);
+        query_as(
+            "select a.*, u.code, u.name from public.audit_log a left join public.user u on a.actor_id = u.id
+            where ($1::uuid is null or a.actor_id = $1)
+            and ($2::text is null or a.entity = $2)
+            and ($3::uuid is null or a.entity_id = $3)
+            and ($4::timestamptz is null or a.create_time >= $4)
+            and ($5::timestamptz is null or a.create_time <= $5)
+            order by a.create_time desc limit $6 offset $7",
+        )
+        .bind(audit_query.actor_id)
+        .bind(&audit_query.entity)
+        .bind(audit_query.entity_id)
+        .bind(audit_query.start_time)
+        .bind(audit_query.end_time)
+        .bind(page_size)
+        .bind(page_size * (page - 1))
+        .fetch_all(pool)
+        .await
+    }
+
+    /// # 分页数据总数
+    pub async fn count(pool: &Pool<Postgres>, audit_query: &AuditQuery) -> i64 {
+        query_scalar(
+            "select count(id) from public.audit_log
+            where ($1::uuid is null or actor_id = $1)
+            and ($2::text is null or entity = $2)
+            and ($3::uuid is null or entity_id = $3)
+            and ($4::timestamptz is null or create_time >= $4)
+            and ($5::timestamptz is null or create_time <= $5)",
+        )
+        .bind(audit_query.actor_id)
+        .bind(&audit_query.entity)
+        .bind(audit_query.entity_id)
+        .bind(audit_query.start_time)
+        .bind(audit_query.end_time)
+        .fetch_one(pool)
+        .await
+        .unwrap_or_default()
+    }
+
+    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into public.audit_log values(DEFAULT, $1, $2, $3, $4, $5, $6, $7, $8, $9)")
+            .bind(self.actor_id)
+            .bind(&self.action)
+            .bind(&self.entity)
+            .bind(self.entity_id)
+            .bind(&self.before)
+            .bind(&self.after)
+            .bind(&self.diff)
+            .bind(&self.address)
+            .bind(self.create_time)
+            .execute(pool)
+            .await
+    }
+
+    /// # 写入审计
+    /// 计算前后差异，失败只记日志不影响业务
+    pub async fn record(mut self, pool: &Pool<Postgres>) {
+        let before = self
+            .before
+            .as_ref()
+            .map(|j| j.0.clone())
+            .unwrap_or_default();
+        let after = self.after.as_ref().map(|j| j.0.clone()).unwrap_or_default();
+        self.diff = Some(Json(json_diff(&before, &after)));
+        match self.insert(pool).await {
+            Ok(_) => log_info!("{}", &self),
+            Err(e) => log_warn!("Audit Log Error {e}"),
+        }
+    }
+}
+
+/// # 前后差异
+/// 对象逐字段比较，只保留变化的字段
+fn json_diff(before: &Value, after: &Value) -> Value {
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            let mut diff = Map::new();
+            for key in b.keys().chain(a.keys().filter(|k| !b.contains_key(*k))) {
+                let bv = b.get(key).unwrap_or(&Value::Null);
+                let av = a.get(key).unwrap_or(&Value::Null);
+                if bv != av {
+                    diff.insert(key.clone(), json!({ "before": bv, "after": av }));
+                }
+            }
+            Value::Object(diff)
+        }
+        _ if before == after => Value::Object(Map::new()),
+        _ => json!({ "before": before, "after": after }),
+    }
+}
+
+#[test]
+fn test_json_diff() {
+    let before = json!({ "name": "a", "status": 1, "tag": "#AAA" });
+    let after = json!({ "name": "b", "status": 1, "phone": "123" });
+    let diff = json_diff(&before, &after);
+    assert_eq!(diff["name"], json!({ "before": "a", "after": "b" }));
+    assert_eq!(diff["tag"], json!({ "before": "#AAA", "after": null }));
+    assert_eq!(diff["phone"], json!({ "before": null, "after": "123" }));
+    assert!(diff.get("status").is_none());
+    assert_eq!(
+        json_diff(&Value::Null, &json!(1)),
+        json!({ "before": null, "after": 1 })
+    );
+}
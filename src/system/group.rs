@@ -1,7 +1,7 @@
-use crate::system::User;
+use crate::system::{User, role::GroupRole};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{Error, FromRow, Pool, Postgres, query_as};
+use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
@@ -30,8 +30,43 @@ impl Group {
             .await
     }
 
+    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let now = Utc::now();
+        query("insert into public.group values(DEFAULT, $1, $2, $2, $3)")
+            .bind(&self.name)
+            .bind(now)
+            .bind(&self.code)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn update(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        let now = Utc::now();
+        query("update public.group set name = $1, code = $2, update_time = $3 where id = $4")
+            .bind(&self.name)
+            .bind(&self.code)
+            .bind(now)
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 删除权限组
+    /// 同时解除用户和权限关联
+    pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        query("delete from public.user_group where group_id = $1")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        GroupRole::delete_group(id, pool).await?;
+        query("delete from public.group where id = $1")
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+
     pub async fn group_users(&self, pool: &Pool<Postgres>) -> Result<Vec<User>, Error> {
-        query_as("select * from public.user u, public.user_group ug where u.id = ug.user_id  and ug.group_id = $1")
+        query_as("select * from public.user u, public.user_group ug where u.id = ug.user_id  and ug.group_id = $1 and u.deleted_at is null")
             .bind(&self.id).fetch_all(pool).await
     }
 }
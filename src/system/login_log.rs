@@ -69,6 +69,14 @@ impl LoginLog {
             .await
     }
 
+    /// # 本人登录记录
+    pub async fn select_user(pool: &Pool<Postgres>, user_id: Uuid) -> Result<Vec<Self>, Error> {
+        query_as("select ll.*, u.code, u.name from public.login_log ll, public.user u where ll.user_id = u.id and ll.user_id = $1 order by login_time desc")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
     pub async fn select_code_or_name(
         pool: &Pool<Postgres>,
         text: String,
@@ -1,11 +1,15 @@
-use crate::{AppState, util::RestApi};
+use crate::{
+    AppState,
+    safety::{ApiKey, ApiScope},
+    util::{RestApi, real_ip},
+};
 use argon2::{Argon2, password_hash::PasswordHasher};
 use axum::{
     Json, Router,
-    extract::{ConnectInfo, Path, State},
+    extract::{ConnectInfo, Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    routing::{get, head, post},
+    routing::{delete, get, head, post, put},
 };
 use axum_auth::AuthBearer;
 use axum_msgpack::MsgPackRaw;
@@ -14,16 +18,20 @@ use std::net::SocketAddr;
 use uuid::Uuid;
 use void_log::{log_info, log_warn};
 
+mod audit_log;
 mod group;
 mod login_log;
 mod redis;
 mod role;
 mod user;
 
+pub use audit_log::{AuditLog, AuditQuery};
 pub use group::Group;
 pub use login_log::LoginLog;
 pub use redis::UserInfo;
+pub use role::{GroupRole, Role};
 pub use user::User;
+use user::{UserGroup, UserPassword, UserProfile};
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -32,7 +40,34 @@ pub fn router() -> Router<AppState> {
         .route("/user_{page}/{page_size}", get(users_page))
         .route("/user_search", post(user_search))
         .route("/user/{id}", get(user).delete(user_delete))
+        .route("/user_archived", get(users_archived))
+        .route("/user_restore/{id}", put(user_restore))
+        .route("/user_purge/{id}", delete(user_purge))
         .route("/get_password/{password}", get(password))
+        .route("/user/{id}/groups", get(user_groups))
+        .route("/user/{id}/roles", get(user_roles))
+        .route(
+            "/user_group",
+            post(user_group_insert).delete(user_group_delete),
+        )
+        // 权限组相关
+        .route("/group", get(groups).post(group_insert).put(group_update))
+        .route("/group/{id}", get(group).delete(group_delete))
+        .route("/group/{id}/roles", get(group_roles))
+        .route("/group/{id}/users", get(group_users))
+        .route(
+            "/group_role",
+            post(group_role_insert).delete(group_role_delete),
+        )
+        // 权限相关
+        .route("/role", get(roles).post(role_insert).put(role_update))
+        .route("/role/{id}", get(role).delete(role_delete))
+        // 个人相关
+        .route("/me", get(me).put(me_update))
+        .route("/me/password", put(me_password))
+        .route("/me/login_log", get(me_login_log))
+        // 审计相关
+        .route("/audit_log", get(audit_logs))
 }
 
 async fn login(
@@ -42,24 +77,23 @@ async fn login(
     AuthBearer(token): AuthBearer,
     MsgPackRaw(data): MsgPackRaw<User>,
 ) -> impl IntoResponse {
-    log_info!("Login Info\n* Token: {}\n* Addr: {}", &token, addr);
+    log_info!(
+        "Login Info\n* ApiKey: {}\n* Addr: {}",
+        ApiKey::key_id(&token),
+        addr
+    );
     // ********************鉴权********************
-    if !token.eq("cfa*login*auth") {
+    if !ApiKey::check(&app_state.pool, &token, ApiScope::LoginAuth).await {
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
 
-    let ip = if let Some(ip) = header_map.get("X-Real-IP") {
-        let ip = ip.to_str().unwrap_or_default();
-        log_info!("IP: {}", ip);
-        ip
-    } else {
-        "NONE"
-    };
+    let ip = real_ip(&header_map);
+    log_info!("IP: {}", ip);
 
     if let Some(check) = data.verify_login(&app_state.pool).await {
         // 添加登陆记录
-        LoginLog::new(check.get_id(), Utc::now(), ip.to_string())
+        LoginLog::new(check.get_id(), Utc::now(), ip)
             .await
             .insert(&app_state.pool)
             .await
@@ -166,17 +200,25 @@ async fn user_search(
 async fn user_insert(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<User>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, Json::default());
-    }
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, Json::default());
+        }
+    };
     // ********************鉴权********************
 
     let res = data.insert(&app_state.pool).await;
     if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "user", &header_map)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
@@ -186,16 +228,27 @@ async fn user_insert(
 async fn user_update(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Json(data): Json<User>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
         return (StatusCode::UNAUTHORIZED, Json::default());
     }
     // ********************鉴权********************
 
     log_info!("Updated: {}", &data);
+    let id = data.id.unwrap_or_default();
+    let before = User::select(&app_state.pool, id).await.ok();
+    let action = if data.password.is_some() {
+        "update_password"
+    } else if data.status.is_some() {
+        "update_status"
+    } else {
+        "update"
+    };
     let res = if data.password.is_some() {
         data.update_password(&app_state.pool).await
     } else if data.status.is_some() {
@@ -205,6 +258,12 @@ async fn user_update(
     };
 
     if let Ok(r) = res {
+        AuditLog::new(&user_info, action, "user", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
@@ -214,23 +273,812 @@ async fn user_update(
 async fn user_delete(
     State(app_state): State<AppState>,
     AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
     Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
     // ********************鉴权********************
-    if let Err(e) = UserInfo::get_user(&token).await {
-        log_warn!("UNAUTHORIZED {e}");
-        return (StatusCode::UNAUTHORIZED, Json::default());
-    }
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, Json::default());
+        }
+    };
     // ********************鉴权********************
 
-    let res = User::delete(&app_state.pool, id).await;
+    let before = User::select(&app_state.pool, id).await.ok();
+    let res = User::delete(&app_state.pool, id, user_info.get_id()).await;
     if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "user", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .after(&User::select(&app_state.pool, id).await.ok())
+            .record(&app_state.pool)
+            .await;
         (StatusCode::OK, Json(r.rows_affected()))
     } else {
         (StatusCode::UNPROCESSABLE_ENTITY, Json::default())
     }
 }
 
+/// # 已归档用户
+async fn users_archived(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = User::select_archived(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 恢复用户
+async fn user_restore(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = User::select(&app_state.pool, id).await.ok();
+    match User::restore(&app_state.pool, id).await {
+        Ok(r) if r.rows_affected() > 0 => {
+            AuditLog::new(&user_info, "restore", "user", &header_map)
+                .entity_id(id)
+                .before(&before)
+                .after(&User::select(&app_state.pool, id).await.ok())
+                .record(&app_state.pool)
+                .await;
+            (StatusCode::OK, RestApi::successful(r.rows_affected()))
+        }
+        _ => (
+            StatusCode::GONE,
+            RestApi::failed("User not archived", "用户不存在或未归档"),
+        ),
+    }
+}
+
+/// # 彻底删除用户
+/// 仍有登录、审计等历史引用时拒绝
+async fn user_purge(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let references = User::references(&app_state.pool, id).await;
+    if references > 0 {
+        return (
+            StatusCode::CONFLICT,
+            RestApi::failed(
+                format!("User still referenced by {references} records"),
+                format!("用户仍有 {references} 条历史记录引用"),
+            ),
+        );
+    }
+    let before = User::select(&app_state.pool, id).await.ok();
+    if let Ok(r) = User::purge(&app_state.pool, id).await {
+        AuditLog::new(&user_info, "purge", "user", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::GONE,
+            RestApi::failed("User not archived", "用户不存在或未归档"),
+        )
+    }
+}
+
+async fn user_groups(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match User::select(&app_state.pool, id).await {
+        Ok(user) => user.user_groups(&app_state.pool).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 用户有效权限
+async fn user_roles(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match User::select(&app_state.pool, id).await {
+        Ok(user) => user.user_roles(&app_state.pool).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn user_group_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<UserGroup>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.insert(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "user_group", &header_map)
+            .entity_id(data.user_id)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn user_group_delete(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<UserGroup>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.delete(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "user_group", &header_map)
+            .entity_id(data.user_id)
+            .before(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn groups(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Group::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn group(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Group::select(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn group_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<Group>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.insert(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "group", &header_map)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn group_update(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<Group>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Group::select(&app_state.pool, data.get_id()).await.ok();
+    let res = data.update(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "update", "group", &header_map)
+            .entity_id(data.get_id())
+            .before(&before)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn group_delete(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Group::select(&app_state.pool, id).await.ok();
+    let res = Group::delete(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "group", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn group_roles(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match Group::select(&app_state.pool, id).await {
+        Ok(group) => group.group_roles(&app_state.pool).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn group_users(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = match Group::select(&app_state.pool, id).await {
+        Ok(group) => group.group_users(&app_state.pool).await,
+        Err(e) => Err(e),
+    };
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn group_role_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<GroupRole>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.insert(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "group_role", &header_map)
+            .entity_id(data.group_id)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn group_role_delete(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<GroupRole>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.delete(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "group_role", &header_map)
+            .entity_id(data.group_id)
+            .before(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn roles(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Role::select_all(&app_state.pool).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn role(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = Role::select(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+async fn role_insert(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<Role>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = data.insert(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "insert", "role", &header_map)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn role_update(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<Role>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Role::select(&app_state.pool, data.get_id()).await.ok();
+    let res = data.update(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "update", "role", &header_map)
+            .entity_id(data.get_id())
+            .before(&before)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+async fn role_delete(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = UserInfo::get_user(&token).await.unwrap_or_default();
+    if !user_info.check_role("admin") {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let before = Role::select(&app_state.pool, id).await.ok();
+    let res = Role::delete(&app_state.pool, id).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "delete", "role", &header_map)
+            .entity_id(id)
+            .before(&before)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Operation Failed", "操作失败"),
+        )
+    }
+}
+
+/// # 个人资料
+async fn me(State(app_state): State<AppState>, AuthBearer(token): AuthBearer) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let user = if let Ok(user) = User::select(&app_state.pool, user_info.get_id()).await {
+        user
+    } else {
+        return (
+            StatusCode::GONE,
+            RestApi::failed("User not found", "用户不存在"),
+        );
+    };
+    let groups = user.user_groups(&app_state.pool).await.unwrap_or_default();
+    let profile = UserProfile {
+        user,
+        clans: user_info.get_clans(),
+        groups,
+        roles: user_info.get_role(),
+    };
+    (StatusCode::OK, RestApi::successful(profile))
+}
+
+/// # 修改个人资料
+/// 只允许修改名称、电话、邮箱
+async fn me_update(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(mut data): Json<User>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    data.id = Some(user_info.get_id());
+    log_info!("Me Updated: {}", &data);
+    let before = User::select(&app_state.pool, user_info.get_id()).await.ok();
+    let res = data.update(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "update", "user", &header_map)
+            .entity_id(user_info.get_id())
+            .before(&before)
+            .after(&data)
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Update Failed", "修改失败"),
+        )
+    }
+}
+
+/// # 修改密码
+/// 需校验原密码
+async fn me_password(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    header_map: HeaderMap,
+    Json(data): Json<UserPassword>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    if data.new_password.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("New password is empty", "新密码不能为空"),
+        );
+    }
+
+    let mut user = User::select(&app_state.pool, user_info.get_id())
+        .await
+        .unwrap_or_default();
+    if !user.check_password(&data.old_password) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            RestApi::failed("Old password incorrect", "原密码错误"),
+        );
+    }
+
+    user.password = Some(data.new_password);
+    let res = user.update_password(&app_state.pool).await;
+    if let Ok(r) = res {
+        AuditLog::new(&user_info, "update_password", "user", &header_map)
+            .entity_id(user_info.get_id())
+            .record(&app_state.pool)
+            .await;
+        (StatusCode::OK, RestApi::successful(r.rows_affected()))
+    } else {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            RestApi::failed("Update Failed", "修改失败"),
+        )
+    }
+}
+
+/// # 本人登录记录
+async fn me_login_log(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    let user_info = match UserInfo::get_user(&token).await {
+        Ok(user_info) => user_info,
+        Err(e) => {
+            log_warn!("UNAUTHORIZED {e}");
+            return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+        }
+    };
+    // ********************鉴权********************
+
+    let res = LoginLog::select_user(&app_state.pool, user_info.get_id()).await;
+    if let Ok(r) = res {
+        (StatusCode::OK, RestApi::successful(r))
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
+/// # 审计查询
+/// 按操作人、对象、时间段筛选
+async fn audit_logs(
+    State(app_state): State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Query(audit_query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    // ********************鉴权********************
+    if !UserInfo::get_user(&token)
+        .await
+        .unwrap_or_default()
+        .check_role("admin")
+    {
+        log_warn!("UNAUTHORIZED");
+        return (StatusCode::UNAUTHORIZED, RestApi::unauthorized());
+    }
+    // ********************鉴权********************
+
+    let res = AuditLog::select_page(&app_state.pool, &audit_query).await;
+    let count = AuditLog::count(&app_state.pool, &audit_query).await;
+    if let Ok(r) = res {
+        (
+            StatusCode::OK,
+            RestApi::new_successful(r).data_count(count).builder(),
+        )
+    } else {
+        (StatusCode::GONE, RestApi::error())
+    }
+}
+
 async fn password(Path(password): Path<String>) -> impl IntoResponse {
     // 密码Hash加密
     let argon2 = Argon2::default();
@@ -39,6 +39,14 @@ impl UserInfo {
         self.id.clone()
     }
 
+    pub fn get_role(&self) -> Vec<Role> {
+        self.role.clone()
+    }
+
+    pub fn get_clans(&self) -> Vec<Clan> {
+        self.clans.clone()
+    }
+
     pub async fn set_user(&self, ex_time: u64) {
         let json_str = serde_json::to_string(&self).unwrap();
         let config = Config::get().await.get_redis();
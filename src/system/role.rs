@@ -1,8 +1,8 @@
+use crate::system::{Group, User};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{query_as, Error, FromRow, Pool, Postgres};
+use sqlx::{Error, FromRow, Pool, Postgres, postgres::PgQueryResult, query, query_as};
 use uuid::Uuid;
-use crate::system::Group;
 
 #[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
 pub struct Role {
@@ -15,10 +15,98 @@ pub struct Role {
     code: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Default, FromRow, Serialize, Deserialize)]
+pub struct GroupRole {
+    pub group_id: Uuid,
+    pub role_id: Uuid,
+}
+
 impl Role {
+    pub fn get_id(&self) -> Uuid {
+        self.id.unwrap_or_default()
+    }
+
     pub fn get_code(&self) -> String {
         self.code.clone().unwrap_or_default()
     }
+
+    pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from public.role").fetch_all(pool).await
+    }
+
+    pub async fn select(pool: &Pool<Postgres>, id: Uuid) -> Result<Self, Error> {
+        query_as("select * from public.role where id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into public.role values(DEFAULT, $1, $2, $3, $4, $5)")
+            .bind(&self.name)
+            .bind(Utc::now())
+            .bind(self.status)
+            .bind(&self.path)
+            .bind(&self.code)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn update(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("update public.role set name = $1, status = $2, path = $3, code = $4 where id = $5")
+            .bind(&self.name)
+            .bind(self.status)
+            .bind(&self.path)
+            .bind(&self.code)
+            .bind(self.id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 删除权限
+    /// 同时解除权限组关联
+    pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        GroupRole::delete_role(id, pool).await?;
+        query("delete from public.role where id = $1")
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+}
+
+impl GroupRole {
+    pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("insert into public.group_role values ($1, $2)")
+            .bind(self.group_id)
+            .bind(self.role_id)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn delete(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("delete from public.group_role where group_id = $1 and role_id = $2")
+            .bind(self.group_id)
+            .bind(self.role_id)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn delete_group(
+        group_id: Uuid,
+        pool: &Pool<Postgres>,
+    ) -> Result<PgQueryResult, Error> {
+        query("delete from public.group_role where group_id = $1")
+            .bind(group_id)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn delete_role(role_id: Uuid, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
+        query("delete from public.group_role where role_id = $1")
+            .bind(role_id)
+            .execute(pool)
+            .await
+    }
 }
 
 impl Group {
@@ -26,4 +114,20 @@ impl Group {
         query_as("select * from public.role r, public.group_role rg where r.id = rg.role_id and rg.group_id = $1")
             .bind(&self.get_id()).fetch_all(pool).await
     }
-}
\ No newline at end of file
+}
+
+impl User {
+    /// # 有效权限
+    /// 所有权限组的权限合并去重
+    pub async fn user_roles(&self, pool: &Pool<Postgres>) -> Result<Vec<Role>, Error> {
+        let mut user_roles: Vec<Role> = vec![];
+        for user_group in self.user_groups(pool).await? {
+            for role in user_group.group_roles(pool).await? {
+                if !user_roles.iter().any(|r| r.get_id() == role.get_id()) {
+                    user_roles.push(role)
+                }
+            }
+        }
+        Ok(user_roles)
+    }
+}
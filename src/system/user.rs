@@ -1,6 +1,6 @@
 use crate::{
     orange::{Clan, ClanUser},
-    system::{UserInfo, role::Role},
+    system::{Group, UserInfo, role::Role},
 };
 use argon2::{
     Argon2,
@@ -29,6 +29,26 @@ pub struct User {
     update_time: DateTime<Utc>,
     #[serde(skip_serializing)]
     pub password: Option<String>,
+    #[serde(skip_deserializing)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub deleted_by: Option<Uuid>,
+}
+
+/// # 个人资料
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub user: User,
+    pub clans: Vec<Clan>,
+    pub groups: Vec<Group>,
+    pub roles: Vec<Role>,
+}
+
+/// # 修改密码
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct UserPassword {
+    pub old_password: String,
+    pub new_password: String,
 }
 
 impl Display for User {
@@ -59,7 +79,9 @@ impl User {
     }
 
     pub async fn select_all(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
-        query_as("select * from public.user").fetch_all(pool).await
+        query_as("select * from public.user where deleted_at is null")
+            .fetch_all(pool)
+            .await
     }
 
     pub async fn select_page(
@@ -67,15 +89,17 @@ impl User {
         page: i64,
         page_size: i64,
     ) -> Result<Vec<Self>, Error> {
-        query_as("select * from public.user order by id limit $1 offset $2")
-            .bind(page_size)
-            .bind(page_size * (page - 1))
-            .fetch_all(pool)
-            .await
+        query_as(
+            "select * from public.user where deleted_at is null order by id limit $1 offset $2",
+        )
+        .bind(page_size)
+        .bind(page_size * (page - 1))
+        .fetch_all(pool)
+        .await
     }
 
     pub async fn count(pool: &Pool<Postgres>) -> i64 {
-        query_scalar("select count(id) from public.user")
+        query_scalar("select count(id) from public.user where deleted_at is null")
             .fetch_one(pool)
             .await
             .unwrap_or_default()
@@ -83,7 +107,7 @@ impl User {
 
     pub async fn select_search(pool: &Pool<Postgres>, text: &str) -> Result<Vec<Self>, Error> {
         let text = format!("%{text}%");
-        query_as("select * from public.user where email like $1 or code like $1 or name like $1")
+        query_as("select * from public.user where (email like $1 or code like $1 or name like $1) and deleted_at is null")
             .bind(text)
             .fetch_all(pool)
             .await
@@ -96,6 +120,14 @@ impl User {
             .await
     }
 
+    /// # 邮箱或编号查询
+    pub async fn select_code(pool: &Pool<Postgres>, code: &str) -> Result<Self, Error> {
+        query_as("select * from public.user where (email = $1 or code = $1) and deleted_at is null")
+            .bind(code)
+            .fetch_one(pool)
+            .await
+    }
+
     pub async fn insert(&self, pool: &Pool<Postgres>) -> Result<PgQueryResult, Error> {
         let now = Utc::now();
         let password = self.get_password_hash();
@@ -144,53 +176,109 @@ impl User {
             .await
     }
 
-    pub async fn delete(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+    /// # 归档用户
+    pub async fn select_archived(pool: &Pool<Postgres>) -> Result<Vec<Self>, Error> {
+        query_as("select * from public.user where deleted_at is not null order by deleted_at desc")
+            .fetch_all(pool)
+            .await
+    }
+
+    /// # 删除（归档）
+    /// 保留权限组和部落关联，归档后不能登录，可恢复
+    pub async fn delete(
+        pool: &Pool<Postgres>,
+        id: Uuid,
+        deleted_by: Uuid,
+    ) -> Result<PgQueryResult, Error> {
+        query("update public.user set deleted_at = $1, deleted_by = $2 where id = $3 and deleted_at is null")
+            .bind(Utc::now())
+            .bind(deleted_by)
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+
+    pub async fn restore(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        query("update public.user set deleted_at = NULL, deleted_by = NULL, update_time = $1 where id = $2 and deleted_at is not null")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await
+    }
+
+    /// # 历史引用数
+    /// 登录、审计、验证、黑名单和入驻审核记录
+    pub async fn references(pool: &Pool<Postgres>, id: Uuid) -> i64 {
+        query_scalar("select (select count(*) from public.login_log where user_id = $1) + (select count(*) from public.audit_log where actor_id = $1) + (select count(*) from orange.clan_verify where user_id = $1) + (select count(*) from orange.blacklist where issue_by = $1) + (select count(*) from orange.clan_apply where review_by = $1)")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// # 彻底删除
+    /// 只删除已归档且无历史引用的用户
+    pub async fn purge(pool: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, Error> {
+        let user = Self::select(pool, id).await?;
+        if user.deleted_at.is_none() || Self::references(pool, id).await > 0 {
+            return Err(Error::RowNotFound);
+        }
         UserGroup::delete_user(id, pool).await?;
         ClanUser::delete_user(id, pool).await?;
+        log_info!("彻底删除 {}", &user);
         query("delete from public.user where id = $1")
             .bind(id)
             .execute(pool)
             .await
     }
 
-    async fn verify_password(
-        &self,
-        password: &str,
-        clans: Vec<Clan>,
-        roles: Vec<Role>,
-    ) -> Option<UserInfo> {
+    /// # 校验密码
+    pub fn check_password(&self, password: &str) -> bool {
         let argon2 = Argon2::default();
-        // 验证密码
-        if let Ok(parsed_hash) = PasswordHash::new(&self.password.clone().unwrap()) {
+        if let Ok(parsed_hash) = PasswordHash::new(&self.password.clone().unwrap_or_default()) {
             log_info!("用户数据校验");
             if let Err(e) = argon2.verify_password(password.as_bytes(), &parsed_hash) {
                 // 校验失败
-                log_warn!("Login failed: {e}");
-                None
+                log_warn!("Password failed: {e}");
+                false
             } else {
-                // 校验成功
-                let timestamp = Utc::now().timestamp();
-                let id = &self.id.unwrap();
-                let code = &self.code.clone().unwrap();
-
-                let token = format!("{}{}{}", timestamp, id, code);
-
-                // 生成登录信息
-                let user_info = UserInfo::new(self.clone(), token, clans, roles);
-                // 存Redis
-                user_info.set_user(3600).await;
-                Some(user_info)
+                true
             }
         } else {
             log_error!("数据库密码存储错误");
+            false
+        }
+    }
+
+    async fn verify_password(
+        &self,
+        password: &str,
+        clans: Vec<Clan>,
+        roles: Vec<Role>,
+    ) -> Option<UserInfo> {
+        // 验证密码
+        if !self.check_password(password) {
             None
+        } else {
+            // 校验成功
+            let timestamp = Utc::now().timestamp();
+            let id = &self.id.unwrap();
+            let code = &self.code.clone().unwrap();
+
+            let token = format!("{}{}{}", timestamp, id, code);
+
+            // 生成登录信息
+            let user_info = UserInfo::new(self.clone(), token, clans, roles);
+            // 存Redis
+            user_info.set_user(3600).await;
+            Some(user_info)
         }
     }
 
     pub async fn verify_login(&self, pool: &Pool<Postgres>) -> Option<UserInfo> {
         // 查用户
         let data_user = query_as::<_, User>(
-            "select * from public.user where (email = $1 or code = $1) and status = 1",
+            "select * from public.user where (email = $1 or code = $1) and status = 1 and deleted_at is null",
         )
         .bind(&self.email)
         .fetch_one(pool)
@@ -199,16 +287,8 @@ impl User {
         log_info!("{}", &data_user);
         // 查部落
         let user_clans = data_user.user_clans(pool).await.unwrap();
-        // 查权限组
-        let user_groups = data_user.user_groups(pool).await.unwrap();
         // 查权限
-        let mut user_roles = vec![];
-        for user_group in user_groups {
-            let mut gr = user_group.group_roles(pool).await.unwrap();
-            user_roles.append(&mut gr)
-        }
-        // 去重
-        user_roles.dedup();
+        let user_roles = data_user.user_roles(pool).await.unwrap();
         // 通过查到的用户数据校验
         data_user
             .verify_password(&self.password.clone().unwrap(), user_clans, user_roles)
@@ -225,6 +305,8 @@ pub struct UserGroup {
 impl UserGroup {
     pub async fn select(&self, pool: &Pool<Postgres>) -> Result<Self, Error> {
         query_as("select * from public.user_group where user_id = $1 and group_id = $2")
+            .bind(self.user_id)
+            .bind(self.group_id)
             .fetch_one(pool)
             .await
     }
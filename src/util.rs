@@ -1,4 +1,4 @@
-use axum::Json;
+use axum::{Json, http::HeaderMap};
 use axum_msgpack::MsgPack;
 use r2d2::PooledConnection;
 use redis::Client;
@@ -140,6 +140,16 @@ impl ConfigApi {
     }
 }
 
+/// # 客户端IP
+/// 取反向代理传入的X-Real-IP
+pub fn real_ip(header_map: &HeaderMap) -> String {
+    header_map
+        .get("X-Real-IP")
+        .and_then(|ip| ip.to_str().ok())
+        .unwrap_or("NONE")
+        .to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestApi<D> {
     msg_en: String,